@@ -0,0 +1,41 @@
+#!/usr/bin/env python3
+"""A minimal provider for testing `icedmenu --provider -- ./examples/provider.py`.
+
+It loads its items once at startup, then answers each query with the items
+that contain it. Messages are newline-delimited JSON on stdin and stdout.
+The tests in src/provider.rs run against this script.
+"""
+import json
+import sys
+
+ITEMS = [
+    {"key": "Firefox", "value": "firefox", "description": "Web browser"},
+    {"key": "Files", "value": "nautilus", "description": "File manager"},
+    {"key": "Terminal", "value": "alacritty", "description": "Terminal emulator"},
+    {"key": "Text Editor", "value": "gedit", "description": "Edit text files"},
+    {"key": "Calculator", "value": "gnome-calculator", "tags": ["math"]},
+]
+
+
+def send(message):
+    sys.stdout.write(json.dumps(message) + "\n")
+    sys.stdout.flush()
+
+
+def main():
+    send({"type": "prompt", "prompt": "Launch:"})
+    send({"type": "message", "message": f"{len(ITEMS)} applications"})
+    for line in sys.stdin:
+        request = json.loads(line)
+        if request["type"] == "query":
+            query = request["query"].lower()
+            if query == "error":
+                send({"type": "error", "message": "Example error"})
+                continue
+            items = [i for i in ITEMS if query in i["key"].lower()]
+            send({"type": "items", "id": request["id"], "items": items})
+        # Queries are answered immediately, so there is nothing to cancel
+
+
+if __name__ == "__main__":
+    main()
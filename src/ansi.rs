@@ -0,0 +1,385 @@
+use iced::Color;
+
+const TAB_WIDTH: usize = 8;
+
+/// A run of text with the same colors and weight
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub color: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+// Colors are parsed from integers, so they are never NaN
+impl Eq for Span {}
+
+pub type Line = Vec<Span>;
+
+/// Splits `text` into lines of colored spans, following SGR escape sequences like
+/// `\x1b[31m`. Other escape sequences are dropped. Colors carry over to the next line,
+/// as they do in a terminal.
+pub fn parse(text: &str) -> Vec<Line> {
+    parse_with(text, false)
+}
+
+/// Like `parse`, but only the escape sequences are removed, so that the text of the spans
+/// is exactly `text` without them. Tabs and newlines are kept as they are.
+pub fn parse_raw(text: &str) -> Line {
+    parse_with(text, true).concat()
+}
+
+fn parse_with(text: &str, raw: bool) -> Vec<Line> {
+    let mut parser = Parser::default();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // Control Sequence Introducer, ending with a byte in `@`..=`~`
+                Some('[') => {
+                    let mut params = String::new();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            if c == 'm' {
+                                parser.apply_sgr(&params);
+                            }
+                            break;
+                        }
+                        params.push(c);
+                    }
+                }
+                // Operating System Command, ending with BEL or `ESC \`
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            c if raw => parser.push(c),
+            '\n' => parser.new_line(),
+            '\t' => {
+                let spaces = expand_tabs("\t", &mut parser.column);
+                parser.span.text.push_str(&spaces);
+            }
+            c if c.is_control() => {}
+            c => parser.push(c),
+        }
+    }
+    parser.finish()
+}
+
+/// Replaces tabs with spaces up to the next tab stop, and drops other control characters.
+/// `column` is where `text` starts in its line, and is moved to where it ends.
+pub fn expand_tabs(text: &str, column: &mut usize) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let n = TAB_WIDTH - *column % TAB_WIDTH;
+                result.extend(std::iter::repeat_n(' ', n));
+                *column += n;
+            }
+            c if c.is_control() => {}
+            c => {
+                result.push(c);
+                *column += 1;
+            }
+        }
+    }
+    result
+}
+
+#[derive(Default)]
+struct Parser {
+    lines: Vec<Line>,
+    line: Line,
+    span: Span,
+    /// The number of characters in the current line, for tab stops
+    column: usize,
+}
+
+impl Parser {
+    fn push(&mut self, c: char) {
+        self.span.text.push(c);
+        self.column += 1;
+    }
+
+    /// Ends the current span, so that the next one starts with the same colors
+    fn end_span(&mut self) {
+        let next = Span {
+            text: String::new(),
+            ..self.span.clone()
+        };
+        let span = std::mem::replace(&mut self.span, next);
+        if !span.text.is_empty() {
+            self.line.push(span);
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.end_span();
+        self.lines.push(std::mem::take(&mut self.line));
+        self.column = 0;
+    }
+
+    fn finish(mut self) -> Vec<Line> {
+        self.end_span();
+        if !self.line.is_empty() {
+            self.lines.push(self.line);
+        }
+        self.lines
+    }
+
+    fn apply_sgr(&mut self, params: &str) {
+        self.end_span();
+        let params: Vec<Option<u8>> = params
+            .split(';')
+            .map(|p| {
+                if p.is_empty() {
+                    Some(0)
+                } else {
+                    p.parse().ok()
+                }
+            })
+            .collect();
+        let mut params = params.into_iter();
+        while let Some(param) = params.next() {
+            match param {
+                Some(0) => self.span = Span::default(),
+                Some(1) => self.span.bold = true,
+                Some(3) => self.span.italic = true,
+                Some(22) => self.span.bold = false,
+                Some(23) => self.span.italic = false,
+                Some(n @ 30..=37) => self.span.color = Some(palette(n - 30)),
+                Some(n @ 90..=97) => self.span.color = Some(palette(n - 90 + 8)),
+                Some(38) => self.span.color = extended_color(&mut params),
+                Some(39) => self.span.color = None,
+                Some(n @ 40..=47) => self.span.background = Some(palette(n - 40)),
+                Some(n @ 100..=107) => self.span.background = Some(palette(n - 100 + 8)),
+                Some(48) => self.span.background = extended_color(&mut params),
+                Some(49) => self.span.background = None,
+                // Underline, blinking and the like can't be shown
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the rest of a 256-color `5;n` or true color `2;r;g;b` parameter
+fn extended_color(params: &mut impl Iterator<Item = Option<u8>>) -> Option<Color> {
+    match params.next()? {
+        Some(5) => Some(palette(params.next()??)),
+        Some(2) => {
+            let mut component = || params.next().flatten();
+            let (r, g, b) = (component()?, component()?, component()?);
+            Some(Color::from_rgb8(r, g, b))
+        }
+        _ => None,
+    }
+}
+
+/// The xterm 256-color palette
+fn palette(n: u8) -> Color {
+    const BASE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match n {
+        0..=15 => {
+            let (r, g, b) = BASE[n as usize];
+            Color::from_rgb8(r, g, b)
+        }
+        16..=231 => {
+            let n = n - 16;
+            Color::from_rgb8(
+                CUBE_LEVELS[(n / 36) as usize],
+                CUBE_LEVELS[(n / 6 % 6) as usize],
+                CUBE_LEVELS[(n % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (n - 232) * 10;
+            Color::from_rgb8(level, level, level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str) -> Span {
+        Span {
+            text: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn colored(text: &str, color: Color) -> Span {
+        Span {
+            color: Some(color),
+            ..span(text)
+        }
+    }
+
+    fn params(s: &str) -> impl Iterator<Item = Option<u8>> + '_ {
+        s.split(';').map(|p| p.parse().ok())
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(parse("a b\nc"), [vec![span("a b")], vec![span("c")]]);
+        assert_eq!(
+            parse("a\n\nb\n"),
+            [vec![span("a")], vec![], vec![span("b")]]
+        );
+        assert_eq!(parse(""), Vec::<Line>::new());
+    }
+
+    #[test]
+    fn sgr() {
+        let red = palette(1);
+        assert_eq!(
+            parse("a\x1b[31mb\x1b[1;3mc\x1b[22;23;39md"),
+            [vec![
+                span("a"),
+                colored("b", red),
+                Span {
+                    bold: true,
+                    italic: true,
+                    ..colored("c", red)
+                },
+                span("d"),
+            ]]
+        );
+        assert_eq!(
+            parse("\x1b[1;41ma\x1b[mb\x1b[0;92;104mc\x1b[49md"),
+            [vec![
+                Span {
+                    bold: true,
+                    background: Some(red),
+                    ..span("a")
+                },
+                span("b"),
+                Span {
+                    background: Some(palette(12)),
+                    ..colored("c", palette(10))
+                },
+                colored("d", palette(10)),
+            ]]
+        );
+        // Underline can't be shown
+        assert_eq!(parse("\x1b[4ma"), [vec![span("a")]]);
+    }
+
+    #[test]
+    fn colors_carry_over_lines() {
+        let red = palette(1);
+        assert_eq!(
+            parse("\x1b[31ma\nb\x1b[0m\nc"),
+            [
+                vec![colored("a", red)],
+                vec![colored("b", red)],
+                vec![span("c")]
+            ]
+        );
+    }
+
+    #[test]
+    fn other_sequences_are_dropped() {
+        assert_eq!(parse("a\x1b[2Kb\x1b[1;5Hc"), [vec![span("abc")]]);
+        assert_eq!(
+            parse("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07!"),
+            [vec![span("link!")]]
+        );
+        // Unfinished and unknown sequences are dropped too
+        assert_eq!(parse("a\x1b[31"), [vec![span("a")]]);
+        assert_eq!(parse("a\x1bb"), [vec![span("a")]]);
+    }
+
+    #[test]
+    fn tabs_and_control_characters() {
+        assert_eq!(parse("a\tb\x07"), [vec![span("a       b")]]);
+        assert_eq!(
+            parse("\x1b[31mabc\x1b[0m\td"),
+            [vec![colored("abc", palette(1)), span("     d")]]
+        );
+        assert_eq!(parse("12345678\tx"), [vec![span("12345678        x")]]);
+        assert_eq!(
+            parse_raw("\x1b[31ma\tb\x1b[0m\n\x07"),
+            [colored("a\tb", palette(1)), span("\n\x07")]
+        );
+    }
+
+    #[test]
+    fn tab_expansion() {
+        let mut column = 3;
+        assert_eq!(expand_tabs("\ta\t\x1b", &mut column), "     a       ");
+        assert_eq!(column, 16);
+    }
+
+    #[test]
+    fn extended_colors() {
+        assert_eq!(extended_color(&mut params("5;196")), Some(palette(196)));
+        assert_eq!(
+            extended_color(&mut params("2;1;2;3")),
+            Some(Color::from_rgb8(1, 2, 3))
+        );
+        assert_eq!(extended_color(&mut params("2;1;2")), None);
+        assert_eq!(extended_color(&mut params("2;1;2;256")), None);
+        assert_eq!(extended_color(&mut params("5")), None);
+        assert_eq!(extended_color(&mut params("5;x")), None);
+        assert_eq!(extended_color(&mut params("7;1")), None);
+        assert_eq!(extended_color(&mut std::iter::empty()), None);
+        // Only the parameters of the color are used
+        let mut rest = params("5;1;1");
+        extended_color(&mut rest);
+        assert_eq!(rest.collect::<Vec<_>>(), [Some(1)]);
+        assert_eq!(
+            parse("\x1b[38;2;1;2;3;48;5;0;1ma"),
+            [vec![Span {
+                color: Some(Color::from_rgb8(1, 2, 3)),
+                background: Some(palette(0)),
+                bold: true,
+                ..span("a")
+            }]]
+        );
+    }
+
+    #[test]
+    fn palette_boundaries() {
+        let rgb = |r, g, b| Color::from_rgb8(r, g, b);
+        assert_eq!(palette(0), rgb(0, 0, 0));
+        assert_eq!(palette(7), rgb(229, 229, 229));
+        assert_eq!(palette(8), rgb(127, 127, 127));
+        assert_eq!(palette(15), rgb(255, 255, 255));
+        // The 6x6x6 color cube
+        assert_eq!(palette(16), rgb(0, 0, 0));
+        assert_eq!(palette(17), rgb(0, 0, 95));
+        assert_eq!(palette(22), rgb(0, 95, 0));
+        assert_eq!(palette(52), rgb(95, 0, 0));
+        assert_eq!(palette(231), rgb(255, 255, 255));
+        // The grayscale ramp
+        assert_eq!(palette(232), rgb(8, 8, 8));
+        assert_eq!(palette(255), rgb(238, 238, 238));
+    }
+}
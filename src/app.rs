@@ -1,143 +1,351 @@
+use crate::ansi::Line;
 use crate::callback::Callback;
-use crate::cli::{CaseSensitivity, CliArgs};
-use crate::config::{LAYOUT_KEY, STYLES_KEY};
-use crate::item::{self, Item};
+use crate::cli::{CustomPolicy, MatchingAlgorithm, SelectionOrder};
+use crate::config::{
+    parse_settings, Settings, KEYBINDINGS_KEY, LAYOUT_KEY, SETTINGS_KEY, STYLES_KEY,
+};
+use crate::editor::QueryEditor;
+use crate::error::MenuError;
+use crate::exit::Exit;
+use crate::input;
+use crate::item::{self, Item, ItemData};
+use crate::keybindings::{self, Action, Chord, Keybindings, Lookup, Mode};
 use crate::layout::{style::parse_styles, LayoutNode};
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
-use iced::keyboard::{self, KeyCode};
-use iced::widget::text_input;
+use crate::menu::{MenuOptions, Selection, SharedOutcome};
+use crate::preview::Preview;
+use crate::provider::{self, Provider};
+use crate::search::{self, Search, SearchField, Searcher};
+use iced::clipboard;
+use iced::keyboard;
+use iced::mouse;
+use iced::widget::{scrollable, text_input};
 use iced::{
-    executor, subscription, window, Application, Command, Element, Event, Subscription, Theme,
+    event, executor, subscription, window, Application, Command, Element, Event, Subscription,
+    Theme,
 };
+use miette::{IntoDiagnostic, WrapErr};
 use std::collections::HashSet;
-use std::error::Error;
-use std::io::{self, Write};
+use std::io;
 use std::path::PathBuf;
 
 pub const DEFAULT_FONT_SIZE: f32 = 16.0;
 
 pub struct IcedMenu {
-    pub cli_args: CliArgs,
+    pub options: MenuOptions,
     pub items: Vec<Item>,
-    pub visible_items: Vec<usize>,
+    /// The indices of all items matching the query, in ranked order
+    pub matched_items: Vec<usize>,
     pub selected_items: Vec<usize>,
-    pub query: String,
+    pub query: QueryEditor,
+    /// Whether the query was submitted but didn't match `--validate`
+    pub query_invalid: bool,
+    pub prompt: String,
+    /// The position of the cursor in `matched_items`
     pub cursor_position: usize,
-    fuzzy_matcher: SkimMatcherV2,
+    /// The position in `matched_items` of the first visible item
+    pub scroll_offset: usize,
+    /// Pixels scrolled by a touchpad that don't make a whole item yet
+    scrolled_pixels: f32,
+    /// Whether items are still being streamed in from stdin
+    pub loading: bool,
+    /// The most recent error, which is shown in the `Status` node
+    pub error: Option<MenuError>,
+    /// A message from the provider, which is shown in the `Status` node
+    pub message: Option<String>,
+    pub settings: Settings,
+    keybindings: Keybindings,
+    /// Whether keys type into the query or move around, as in vim
+    pub mode: Mode,
+    /// The keys pressed so far of a sequence like `g g`
+    pending_keys: Vec<Chord>,
+    /// Whether the last key pressed was bound or a command like Ctrl-V, so the character it
+    /// sends afterwards isn't typed into the query
+    ignore_char: bool,
+    searcher: Searcher,
+    /// The query, parsed for the searcher whenever it changes
+    search: Search,
     callback: Option<Callback>,
+    /// Incremented for every callback run, so that stale results can be discarded
+    callback_generation: usize,
+    callback_pending: bool,
+    provider: Option<Provider>,
+    /// Runs the command of the `Preview` node, if the layout has one
+    preview: Option<Preview>,
+    parse_options: item::ParseOptions,
     layout: LayoutNode,
+    outcome: SharedOutcome,
 }
 
 impl IcedMenu {
+    /// Matches the items against a new query, moving the cursor back to the first one
     fn update_items(&mut self) {
-        if let Some(callback) = &mut self.callback {
-            let new_items = callback.call(&self.query);
-            let cur_selected_items: Vec<Item> = self
-                .selected_items
-                .iter()
-                .map(|i| self.items[*i].clone())
-                .collect();
-            let selected_keys: HashSet<String> = cur_selected_items
-                .iter()
-                .map(|item| item.data.key.clone())
-                .collect();
-            self.items = new_items
+        self.query_invalid = false;
+        self.move_cursor(CursorMoveDirection::Reset);
+        if let Some(provider) = &mut self.provider {
+            provider.query(self.query.text());
+            return;
+        }
+        if self.callback.is_some() {
+            // The callback runs in the background and its results arrive as a message
+            self.callback_generation += 1;
+            self.callback_pending = true;
+            return;
+        }
+        self.search = Search::parse(self.query.text(), self.searcher.algorithm);
+        self.score_items(0);
+        self.rank_items();
+    }
+
+    fn replace_items(&mut self, new_items: Vec<Item>) {
+        let cur_selected_items: Vec<Item> = self
+            .selected_items
+            .iter()
+            .map(|i| self.items[*i].clone())
+            .collect();
+        let selected_keys: HashSet<String> = cur_selected_items
+            .iter()
+            .map(|item| item.data.key.clone())
+            .collect();
+        self.items = new_items
+            .into_iter()
+            .filter(|item| !selected_keys.contains(&item.data.key))
+            .enumerate()
+            .map(|(i, mut item)| {
+                item.index = i;
+                item
+            })
+            .collect();
+        let n_new_items = self.items.len();
+        let mut new_selected = Vec::new();
+        self.items.extend(
+            cur_selected_items
                 .into_iter()
-                .filter(|item| !selected_keys.contains(&item.data.key))
-                .take(self.cli_args.max_visible - cur_selected_items.len())
                 .enumerate()
                 .map(|(i, mut item)| {
-                    item.index = i;
+                    item.index = i + n_new_items;
+                    new_selected.push(item.index);
                     item
-                })
-                .collect();
-            let n_new_items = self.items.len();
-            let mut new_selected = Vec::new();
-            self.items.extend(
-                cur_selected_items
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, mut item)| {
-                        item.index = i + n_new_items;
-                        new_selected.push(item.index);
-                        item
-                    }),
-            );
-            self.selected_items = new_selected;
-            self.visible_items = self.items.iter().map(|item| item.index).collect();
-            return;
+                }),
+        );
+        self.selected_items = new_selected;
+        self.matched_items = self.items.iter().map(|item| item.index).collect();
+        self.clamp_cursor();
+    }
+
+    /// Scores the items starting from `start` against the current query
+    fn score_items(&mut self, start: usize) {
+        self.searcher
+            .score_items(&self.search, &mut self.items[start..]);
+    }
+
+    fn rank_items(&mut self) {
+        // Already-selected items are kept at the end
+        self.matched_items = search::rank_items(&self.search, &self.items)
+            .into_iter()
+            .filter(|i| !self.selected_items.contains(i))
+            .chain(self.selected_items.iter().copied())
+            .collect();
+        self.clamp_cursor();
+    }
+
+    /// The items that fit in the window at the current scroll position
+    pub fn visible_items(&self) -> &[usize] {
+        let end = (self.scroll_offset + self.options.max_visible).min(self.matched_items.len());
+        &self.matched_items[self.scroll_offset.min(end)..end]
+    }
+
+    fn max_scroll_offset(&self) -> usize {
+        self.matched_items
+            .len()
+            .saturating_sub(self.options.max_visible)
+    }
+
+    /// Keeps the cursor and scroll position within the matched items after they change
+    fn clamp_cursor(&mut self) {
+        self.cursor_position = self
+            .cursor_position
+            .min(self.matched_items.len().saturating_sub(1));
+        self.scroll_offset = self.scroll_offset.min(self.max_scroll_offset());
+        self.scroll_to_cursor();
+    }
+
+    /// Scrolls just far enough for the cursor to be visible
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor_position < self.scroll_offset {
+            self.scroll_offset = self.cursor_position;
+        } else if self.cursor_position >= self.scroll_offset + self.options.max_visible {
+            self.scroll_offset = self.cursor_position + 1 - self.options.max_visible;
         }
-        self.items.iter_mut().for_each(|item| {
-            if self.query.is_empty() || item.selected {
-                item.score = None;
-                item.match_indices = None;
-                return;
-            }
-            match self
-                .fuzzy_matcher
-                .fuzzy_indices(&item.data.key, &self.query)
-            {
-                Some((score, match_indices)) => {
-                    item.score = Some(score as u32);
-                    item.match_indices = Some(match_indices);
-                }
-                None => {
-                    item.score = None;
-                    item.match_indices = None;
+    }
+
+    /// How many items a scroll event moves by. Scrolling up moves towards the first item.
+    /// Touchpads scroll by pixels, which are added up until they make a whole item.
+    fn scrolled_rows(&mut self, delta: mouse::ScrollDelta) -> isize {
+        match delta {
+            mouse::ScrollDelta::Lines { y, .. } => -y.round() as isize,
+            mouse::ScrollDelta::Pixels { y, .. } => {
+                let item_height = self.item_height();
+                self.scrolled_pixels -= y;
+                let rows = (self.scrolled_pixels / item_height).trunc();
+                self.scrolled_pixels -= rows * item_height;
+                rows as isize
+            }
+        }
+    }
+
+    /// The average height of the visible items
+    fn item_height(&self) -> f32 {
+        let heights = match LayoutNode::items(&self.layout) {
+            Some(data) => crate::layout::items::heights(data, self),
+            None => Vec::new(),
+        };
+        if heights.is_empty() {
+            return DEFAULT_FONT_SIZE;
+        }
+        (heights.iter().sum::<u32>() as f32 / heights.len() as f32).max(1.0)
+    }
+
+    /// Scrolls by a number of items without moving the cursor out of view
+    fn scroll(&mut self, delta: isize) {
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(delta)
+            .min(self.max_scroll_offset());
+        let last_visible = (self.scroll_offset + self.options.max_visible)
+            .min(self.matched_items.len())
+            .saturating_sub(1);
+        self.cursor_position = self
+            .cursor_position
+            .clamp(self.scroll_offset, last_visible.max(self.scroll_offset));
+    }
+
+    fn add_items(&mut self, new_items: Vec<ItemData>) {
+        let start = self.items.len();
+        self.items.extend(
+            new_items
+                .into_iter()
+                .enumerate()
+                .map(|(i, data)| Item::new(start + i, data)),
+        );
+        self.score_items(start);
+        self.rank_items();
+    }
+
+    /// Resizes the window to fit its contents if the number of visible items has changed
+    fn resize_if_needed(&mut self, num_items_prev: usize) -> Command<Message> {
+        if self.visible_items().len() != num_items_prev {
+            self.resize()
+        } else {
+            Command::none()
+        }
+    }
+
+    /// The size of the window needed to fit the layout
+    pub fn size(&self) -> (u32, u32) {
+        (
+            LayoutNode::width(&self.layout, self, None),
+            LayoutNode::height(&self.layout, self, None),
+        )
+    }
+
+    fn resize(&self) -> Command<Message> {
+        let (width, height) = self.size();
+        window::resize::<Message>(width, height)
+    }
+
+    fn handle_provider_event(&mut self, event: provider::Event) -> Command<Message> {
+        let Some(provider) = &mut self.provider else {
+            return Command::none();
+        };
+        let command = match event {
+            provider::Event::Ready(sender) => {
+                provider.connect(sender);
+                return Command::none();
+            }
+            provider::Event::Items(id, new_items) => {
+                // Items for an outdated query are dropped
+                if !provider.accept(id) {
+                    return Command::none();
                 }
+                self.error = None;
+                self.replace_items(new_items);
+                Command::none()
             }
-        });
-        let mut candidates: Vec<&Item> = self
-            .items
-            .iter()
-            .filter(|item| self.query.is_empty() || item.score.is_some())
-            .collect();
-        candidates.sort_by(|a, b| b.cmp(a));
+            provider::Event::Prompt(prompt) => {
+                self.prompt = prompt;
+                Command::none()
+            }
+            provider::Event::Message(message) => {
+                self.message = Some(message);
+                Command::none()
+            }
+            provider::Event::Error(e) | provider::Event::Exited(e) => self.show_error(e),
+        };
+        // The prompt and status may have changed size as well as the items
+        Command::batch(vec![command, self.resize()])
+    }
 
-        // Make room for already-selected items, and add them at the end
-        self.visible_items = candidates
-            .iter()
-            .map(|item| &item.index)
-            .filter(|&i| !self.selected_items.contains(i))
-            .take(self.cli_args.max_visible - self.selected_items.len())
-            .chain(self.selected_items.iter())
-            .copied()
-            .collect();
+    /// Shows an error in the `Status` node, or closes the menu with it if errors are fatal
+    fn show_error(&mut self, error: MenuError) -> Command<Message> {
+        if self.settings.fatal_errors {
+            return self.close(Err(error));
+        }
+        self.error = Some(error);
+        Command::none()
+    }
+
+    /// Closes the window, after which `MenuBuilder::run` returns `outcome`
+    fn close(&self, outcome: Result<Selection, MenuError>) -> Command<Message> {
+        // Only the first outcome counts if more keys arrive before the window closes
+        self.outcome.lock().unwrap().get_or_insert(outcome);
+        window::close()
     }
 
     fn move_cursor(&mut self, direction: CursorMoveDirection) {
-        let num_items = self.visible_items.len();
+        let num_items = self.matched_items.len();
+        if num_items == 0 {
+            self.cursor_position = 0;
+            self.scroll_offset = 0;
+            return;
+        }
+        let last = num_items - 1;
+        let page = self.options.max_visible.max(1);
         self.cursor_position = match direction {
+            // Moving past either end wraps around
             CursorMoveDirection::Up => {
                 if self.cursor_position == 0 {
-                    num_items - 1
+                    last
                 } else {
                     self.cursor_position - 1
                 }
             }
             CursorMoveDirection::Down => {
-                if self.cursor_position == (num_items - 1) {
+                if self.cursor_position == last {
                     0
                 } else {
                     self.cursor_position + 1
                 }
             }
-            CursorMoveDirection::Reset => 0,
+            CursorMoveDirection::PageUp => self.cursor_position.saturating_sub(page),
+            CursorMoveDirection::PageDown => (self.cursor_position + page).min(last),
+            CursorMoveDirection::First | CursorMoveDirection::Reset => 0,
+            CursorMoveDirection::Last => last,
         };
+        self.scroll_to_cursor();
     }
 
     fn update_selection(&mut self, index: usize, change: SelectionChange) {
-        let mut item = &mut self.items[index];
+        let item = &mut self.items[index];
         match change {
             SelectionChange::Select => {
-                if self.selected_items.len() < self.cli_args.max {
+                if self.selected_items.len() < self.options.max {
                     self.selected_items.push(index);
                     item.selected = true;
                 }
             }
             SelectionChange::Deselect => {
-                self.selected_items.swap_remove(
+                // Keep the order of selection for `--selection-order selection`
+                self.selected_items.remove(
                     self.selected_items
                         .iter()
                         .position(|x| *x == index)
@@ -152,34 +360,232 @@ impl IcedMenu {
         };
     }
 
-    pub fn index_under_cursor(&self) -> usize {
-        self.visible_items[self.cursor_position]
+    /// Handles a key chord, keeping the items and the query input in sync with the query
+    fn handle_key(&mut self, chord: Chord) -> Command<Message> {
+        if chord.is_modifier() {
+            return Command::none();
+        }
+        self.ignore_char = chord.is_command();
+        self.pending_keys.push(chord);
+        let action = match self.keybindings.lookup(self.mode, &self.pending_keys) {
+            Lookup::Action(action) => action.clone(),
+            // Wait for the rest of a sequence like `g g`
+            Lookup::Prefix => {
+                self.ignore_char = true;
+                return Command::none();
+            }
+            Lookup::None => {
+                // An unfinished sequence is dropped, but its last key may be bound on its own
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                return if retry {
+                    self.handle_key(chord)
+                } else {
+                    Command::none()
+                };
+            }
+        };
+        self.ignore_char = true;
+        let keys = std::mem::take(&mut self.pending_keys);
+        self.edit_query(|menu| menu.perform(action, &keys))
     }
 
-    fn submit(&self) {
-        let selected_items: Vec<&Item> = self.items.iter().filter(|item| item.selected).collect();
-        io::stdout()
-            .write_all(
-                (selected_items
-                    .iter()
-                    .map(|item| match &item.data.value {
-                        Some(value) => value.clone(),
-                        None => item.data.key.clone(),
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n"))
-                .as_bytes(),
-            )
-            .unwrap();
+    /// Types a character into the query, unless it was sent by a key that was handled
+    /// already. Keys are always received before the characters they send.
+    fn type_char(&mut self, c: char) -> Command<Message> {
+        if std::mem::take(&mut self.ignore_char) || self.mode != Mode::Insert || c.is_control() {
+            return Command::none();
+        }
+        self.edit_query(|menu| {
+            menu.query.insert(c.encode_utf8(&mut [0; 4]));
+            Command::none()
+        })
     }
-}
 
-fn new_matcher(cli_args: &CliArgs) -> SkimMatcherV2 {
-    let matcher = SkimMatcherV2::default();
-    match cli_args.case {
-        CaseSensitivity::Smart => matcher.smart_case(),
-        CaseSensitivity::Respect => matcher.respect_case(),
-        CaseSensitivity::Ignore => matcher.ignore_case(),
+    /// Runs something that may edit the query, then updates the items if the query changed
+    /// and moves the cursor of the query input to the editor's
+    fn edit_query(&mut self, edit: impl FnOnce(&mut Self) -> Command<Message>) -> Command<Message> {
+        let num_items_prev = self.visible_items().len();
+        let query_prev = self.query.text().to_owned();
+        let command = edit(self);
+        let resize = if self.query.text() != query_prev {
+            self.update_items();
+            self.resize_if_needed(num_items_prev)
+        } else {
+            Command::none()
+        };
+        Command::batch(vec![
+            command,
+            resize,
+            text_input::move_cursor_to(
+                text_input::Id::new(crate::layout::query::QUERY_INPUT_ID),
+                self.query.cursor(),
+            ),
+        ])
+    }
+
+    /// Performs an action bound to `keys`
+    fn perform(&mut self, action: Action, keys: &[Chord]) -> Command<Message> {
+        match action {
+            Action::Up => self.move_cursor(CursorMoveDirection::Up),
+            Action::Down => self.move_cursor(CursorMoveDirection::Down),
+            Action::PageUp => self.move_cursor(CursorMoveDirection::PageUp),
+            Action::PageDown => self.move_cursor(CursorMoveDirection::PageDown),
+            Action::First => self.move_cursor(CursorMoveDirection::First),
+            Action::Last => self.move_cursor(CursorMoveDirection::Last),
+            Action::Toggle => {
+                if let Some(index) = self.index_under_cursor() {
+                    self.update_selection(index, SelectionChange::Toggle);
+                }
+            }
+            Action::SelectAll => {
+                for index in self.matched_items.clone() {
+                    if !self.items[index].selected {
+                        self.update_selection(index, SelectionChange::Select);
+                    }
+                }
+            }
+            Action::DeselectAll => {
+                for index in self.selected_items.clone() {
+                    self.update_selection(index, SelectionChange::Deselect);
+                }
+            }
+            Action::Submit | Action::Custom(_) => {
+                let Some(index) = self.index_under_cursor() else {
+                    // Enter submits the query instead when nothing matches, if allowed
+                    if action == Action::Submit
+                        && self.options.allow_custom == CustomPolicy::NoMatch
+                    {
+                        return self.accept_query(keys);
+                    }
+                    return Command::none();
+                };
+                self.update_selection(index, SelectionChange::Select);
+                let exit = match action {
+                    Action::Custom(n) => Exit::Custom(n),
+                    _ => Exit::Accept,
+                };
+                return self.submit(exit, &keybindings::format_keys(keys));
+            }
+            Action::AcceptQuery => return self.accept_query(keys),
+            Action::ClearQuery => self.query.clear(),
+            Action::BeginningOfLine => self.query.beginning_of_line(),
+            Action::EndOfLine => self.query.end_of_line(),
+            Action::BackwardChar => self.query.backward_char(),
+            Action::ForwardChar => self.query.forward_char(),
+            Action::BackwardWord => self.query.backward_word(),
+            Action::ForwardWord => self.query.forward_word(),
+            Action::BackwardDeleteChar => self.query.backward_delete_char(),
+            Action::DeleteChar => self.query.delete_char(),
+            Action::UnixWordRubout => self.query.unix_word_rubout(),
+            Action::BackwardKillWord => self.query.backward_kill_word(),
+            Action::KillWord => self.query.kill_word(),
+            Action::UnixLineDiscard => self.query.unix_line_discard(),
+            Action::KillLine => self.query.kill_line(),
+            Action::Yank => self.query.yank(),
+            Action::YankPop => self.query.yank_pop(),
+            Action::Undo => self.query.undo(),
+            Action::Redo => self.query.redo(),
+            Action::Paste => return clipboard::read(Message::Pasted),
+            Action::NormalMode => self.mode = Mode::Normal,
+            Action::InsertMode => {
+                self.mode = Mode::Insert;
+                // Escape unfocuses the query input
+                return text_input::focus(text_input::Id::new(
+                    crate::layout::query::QUERY_INPUT_ID,
+                ));
+            }
+            Action::Quit => {
+                return self.finish(Exit::Cancel, Vec::new(), &keybindings::format_keys(keys))
+            }
+            Action::Execute(command) => {
+                let item = match self.index_under_cursor() {
+                    Some(index) => {
+                        let data = &self.items[index].data;
+                        data.value.clone().unwrap_or_else(|| data.key.clone())
+                    }
+                    None => String::new(),
+                };
+                return Command::perform(
+                    keybindings::execute(command, item, self.query.text().to_owned()),
+                    Message::Executed,
+                );
+            }
+        }
+        Command::none()
+    }
+
+    /// Runs the preview command for the item under the cursor, if it has moved to another one.
+    /// The preview is scrolled back to the top when it shows something new.
+    fn update_preview(&mut self) -> Command<Message> {
+        let index = self.index_under_cursor();
+        let Some(preview) = &mut self.preview else {
+            return Command::none();
+        };
+        if preview.show(index.map(|i| &self.items[i].data)) {
+            Self::scroll_preview_to_top()
+        } else {
+            Command::none()
+        }
+    }
+
+    fn scroll_preview_to_top() -> Command<Message> {
+        scrollable::snap_to(
+            scrollable::Id::new(crate::layout::preview::PREVIEW_SCROLLABLE_ID),
+            scrollable::RelativeOffset::START,
+        )
+    }
+
+    /// The output of the preview command that is shown in the `Preview` node, or why it
+    /// could not be run
+    pub fn preview_output(&self) -> Option<Result<&[Line], &MenuError>> {
+        self.preview.as_ref().and_then(Preview::output)
+    }
+
+    /// The item under the cursor, unless nothing matches the query
+    pub fn index_under_cursor(&self) -> Option<usize> {
+        self.matched_items.get(self.cursor_position).copied()
+    }
+
+    /// Submits the query itself, if `--allow-custom` and `--validate` allow it
+    fn accept_query(&mut self, keys: &[Chord]) -> Command<Message> {
+        if self.options.allow_custom == CustomPolicy::Never {
+            return Command::none();
+        }
+        if let Some(pattern) = &self.options.validate {
+            if !pattern.is_match(self.query.text()) {
+                self.query_invalid = true;
+                return Command::none();
+            }
+        }
+        self.finish(
+            Exit::AcceptQuery,
+            Vec::new(),
+            &keybindings::format_keys(keys),
+        )
+    }
+
+    /// Submits the selected items, in the order they were selected or in the order of the
+    /// items, depending on `selection_order`
+    fn submit(&self, exit: Exit, keys: &str) -> Command<Message> {
+        let mut items: Vec<Item> = self
+            .selected_items
+            .iter()
+            .map(|&i| self.items[i].clone())
+            .collect();
+        if self.options.selection_order == SelectionOrder::Input {
+            items.sort_by_key(|item| item.index);
+        }
+        self.finish(exit, items, keys)
+    }
+
+    fn finish(&self, exit: Exit, items: Vec<Item>, keys: &str) -> Command<Message> {
+        self.close(Ok(Selection {
+            exit,
+            items,
+            query: self.query.text().to_owned(),
+            keys: keys.to_owned(),
+        }))
     }
 }
 
@@ -187,6 +593,10 @@ fn new_matcher(cli_args: &CliArgs) -> SkimMatcherV2 {
 pub enum CursorMoveDirection {
     Up,
     Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
     Reset,
 }
 
@@ -209,61 +619,157 @@ impl SelectionChange {
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    /// Replaces the whole query
     QueryChanged(String),
-    CursorMoved(CursorMoveDirection),
-    CursorSelectionToggled,
+    /// A character was typed, which goes into the query in insert mode
+    CharacterReceived(char),
+    /// The text in the clipboard, for the `paste` action
+    Pasted(Option<String>),
+    ItemsReceived(Vec<ItemData>),
+    InputClosed(Option<MenuError>),
+    CallbackFinished(usize, Result<Vec<Item>, MenuError>),
+    Provider(provider::Event),
+    KeyPressed(Chord),
+    /// The mouse wheel or touchpad scrolled over the items
+    Scrolled(mouse::ScrollDelta),
     MouseClicked(usize),
-    Submitted,
-    Quit,
+    /// An `execute` action has finished
+    Executed(Result<(), MenuError>),
+    /// The preview command, with the item filled in, has finished
+    PreviewFinished(String, Result<Vec<Line>, MenuError>),
 }
 
 pub struct Flags {
-    pub cli_args: CliArgs,
+    pub options: MenuOptions,
     pub items: Vec<Item>,
     pub layout: LayoutNode,
+    pub settings: Settings,
+    pub keybindings: Keybindings,
     pub callback: Option<Callback>,
+    pub provider: Option<Provider>,
+    pub parse_options: item::ParseOptions,
+    /// Whether items should be streamed in from stdin
+    pub stream_input: bool,
+    /// An error that happened while reading the initial items
+    pub error: Option<MenuError>,
+    /// Where the menu leaves what it was closed with
+    pub outcome: SharedOutcome,
 }
 
 impl Flags {
-    pub fn new(cli_args: CliArgs) -> Self {
-        let mut callback = cli_args.callback.clone().map(Callback::new);
-        Self {
-            items: Self::get_items(&cli_args.file, &cli_args.query, &mut callback)
-                .expect("Error while parsing items"),
-            layout: Self::get_layout(&cli_args.config).unwrap(),
+    /// Reads the config from the `config` option, and any items from the `file` option
+    pub fn new(options: MenuOptions) -> miette::Result<Self> {
+        let (source_name, source) = Self::read_config(&options.config)?;
+        Self::with_config(options, &source_name, source)
+    }
+
+    /// Like `new`, but with the config given as KDL source
+    pub fn with_config(
+        options: MenuOptions,
+        source_name: &str,
+        source: String,
+    ) -> miette::Result<Self> {
+        let parse_options = item::ParseOptions::new(&options);
+        let (callback, provider) = match options.callback.clone() {
+            Some(args) if options.provider => (None, Some(Provider::new(args))),
+            Some(args) => (
+                Some(Callback::new(args, parse_options.clone(), options.debounce)),
+                None,
+            ),
+            None => (None, None),
+        };
+        let (layout, settings, keybindings) = Self::parse_config(source_name, source, options.vim)?;
+        let (items, error) = match Self::get_items(&options.file, &parse_options) {
+            Ok(items) => (items, None),
+            Err(e) => (Vec::new(), Some(MenuError::Input(e.to_string()))),
+        };
+        Ok(Self {
+            items,
+            error,
+            layout,
+            settings,
+            keybindings,
+            stream_input: options.file.is_none() && options.callback.is_none(),
             callback,
-            cli_args,
+            provider,
+            parse_options,
+            outcome: SharedOutcome::default(),
+            options,
+        })
+    }
+
+    pub fn searcher(&self) -> Searcher {
+        Searcher::new(
+            self.options
+                .matching
+                .or(self.settings.matching)
+                .unwrap_or(MatchingAlgorithm::Fuzzy),
+            self.options.case,
+            self.search_fields(),
+            self.options.nth.clone(),
+            self.options.delimiter,
+            self.options.normalize || self.settings.normalize,
+        )
+    }
+
+    /// Reads all items from stdin up front, instead of streaming them into the menu
+    pub fn read_stdin(&mut self) {
+        if !self.stream_input {
+            return;
+        }
+        self.stream_input = false;
+        match item::parse_items(io::stdin(), &self.parse_options) {
+            Ok(items) => self.items = items,
+            Err(e) => self.error = Some(MenuError::Input(e.to_string())),
+        }
+    }
+
+    /// The item fields to search: from `--search-field`, the config, or just the key
+    fn search_fields(&self) -> Vec<SearchField> {
+        if !self.options.search_fields.is_empty() {
+            self.options.search_fields.clone()
+        } else if !self.settings.search_field.is_empty() {
+            self.settings.search_field.clone()
+        } else {
+            vec![SearchField::new("key")]
         }
     }
 
     fn get_items(
         path: &Option<PathBuf>,
-        query: &str,
-        callback: &mut Option<Callback>,
-    ) -> Result<Vec<Item>, Box<dyn Error>> {
-        match (path, callback) {
-            (Some(p), _) => {
+        parse_options: &item::ParseOptions,
+    ) -> item::ParseResult<Vec<Item>> {
+        match path {
+            Some(p) => {
                 let source = std::fs::File::open(p)?;
-                Ok(item::parse_items(source)?)
+                Ok(item::parse_items(source, parse_options)?)
             }
-            (None, Some(c)) => Ok(c.call(query)),
-            (None, None) => {
-                let source = io::stdin();
-                Ok(item::parse_items(source)?)
+            // Items from stdin, a callback, or a provider arrive after the menu opens
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The name and contents of the config file, or of the default config
+    fn read_config(path: &Option<PathBuf>) -> miette::Result<(String, String)> {
+        match path {
+            Some(p) => {
+                let source = std::fs::read_to_string(p)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Could not read {}", p.display()))?;
+                Ok((p.display().to_string(), source))
             }
+            None => Ok((
+                String::from("(default config)"),
+                include_str!("../default-config.kdl").to_string(),
+            )),
         }
     }
 
-    fn get_layout(path: &Option<PathBuf>) -> miette::Result<LayoutNode> {
-        let source_path;
-        let source;
-        if let Some(p) = path {
-            source_path = p.to_str().expect("Non-unicode path encountered");
-            source = std::fs::read_to_string(p).expect("Could not read file");
-        } else {
-            source_path = "(default config)";
-            source = include_str!("../default-config.kdl").to_string();
-        };
+    fn parse_config(
+        source_name: &str,
+        source: String,
+        vim: bool,
+    ) -> miette::Result<(LayoutNode, Settings, Keybindings)> {
         let config: kdl::KdlDocument = source.parse()?;
         let layout_definition = config
             .get(LAYOUT_KEY)
@@ -275,10 +781,23 @@ impl Flags {
         let wrap_error = |e| {
             miette::Report::from(e)
                 .wrap_err("Could not read config file")
-                .with_source_code(miette::NamedSource::new(source_path, source.to_owned()))
+                .with_source_code(miette::NamedSource::new(source_name, source.to_owned()))
         };
         let styles = parse_styles(styles_definition).map_err(wrap_error)?;
-        LayoutNode::new(layout_definition, &styles).map_err(wrap_error)
+        let layout = LayoutNode::new(layout_definition, &styles).map_err(wrap_error)?;
+        let mut settings = match config.get(SETTINGS_KEY) {
+            Some(settings_definition) => parse_settings(settings_definition).map_err(wrap_error)?,
+            None => Settings::default(),
+        };
+        settings.vim_mode |= vim;
+        let keybindings = match config.get(KEYBINDINGS_KEY) {
+            Some(keybindings_definition) => {
+                keybindings::parse_keybindings(keybindings_definition, settings.vim_mode)
+                    .map_err(wrap_error)?
+            }
+            None => Keybindings::new(settings.vim_mode),
+        };
+        Ok((layout, settings, keybindings))
     }
 }
 
@@ -290,24 +809,56 @@ impl Application for IcedMenu {
 
     fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
         let query_input_id = text_input::Id::new(crate::layout::query::QUERY_INPUT_ID);
+        let searcher = flags.searcher();
         let mut menu = Self {
-            fuzzy_matcher: new_matcher(&flags.cli_args),
-            query: flags.cli_args.query.clone(),
+            search: Search::parse(&flags.options.query, searcher.algorithm),
+            searcher,
+            query: QueryEditor::new(&flags.options.query),
+            prompt: flags.options.prompt.clone(),
             items: flags.items,
             callback: flags.callback,
-            cli_args: flags.cli_args,
+            callback_generation: 0,
+            callback_pending: false,
+            provider: flags.provider,
+            preview: LayoutNode::preview(&flags.layout).map(|data| {
+                Preview::new(
+                    data.command.clone(),
+                    data.max_bytes,
+                    data.style.syntax_theme,
+                )
+            }),
+            options: flags.options,
+            parse_options: flags.parse_options,
+            loading: flags.stream_input,
+            error: None,
+            message: None,
+            settings: flags.settings,
+            keybindings: flags.keybindings,
+            query_invalid: false,
+            mode: Mode::Insert,
+            pending_keys: Vec::new(),
+            ignore_char: false,
             layout: flags.layout,
-            visible_items: Vec::new(),
+            outcome: flags.outcome,
+            matched_items: Vec::new(),
             selected_items: Vec::new(),
             cursor_position: 0,
+            scroll_offset: 0,
+            scrolled_pixels: 0.0,
+        };
+        let error = match flags.error {
+            Some(e) => menu.show_error(e),
+            None => Command::none(),
         };
         menu.update_items();
-        let width = LayoutNode::width(&menu.layout, &menu, None);
-        let height = LayoutNode::height(&menu.layout, &menu, None);
+        let resize = menu.resize();
+        let preview = menu.update_preview();
         (
             menu,
             Command::batch(vec![
-                window::resize::<Message>(width, height),
+                error,
+                resize,
+                preview,
                 text_input::focus(query_input_id),
                 window::gain_focus(),
             ]),
@@ -315,7 +866,7 @@ impl Application for IcedMenu {
     }
 
     fn title(&self) -> String {
-        self.cli_args.prompt.clone()
+        self.prompt.clone()
     }
 
     fn theme(&self) -> Self::Theme {
@@ -325,71 +876,140 @@ impl Application for IcedMenu {
         })
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         LayoutNode::view(&self.layout, self, None)
     }
 
     fn update(&mut self, message: Message) -> Command<Self::Message> {
-        match message {
-            Message::QueryChanged(new_query) => {
-                let num_items_prev = self.visible_items.len();
-                self.query = new_query;
-                self.update_items();
-                let num_items = self.visible_items.len();
-                if num_items != num_items_prev {
-                    self.move_cursor(CursorMoveDirection::Reset);
-                    window::resize::<Message>(
-                        LayoutNode::width(&self.layout, self, None),
-                        LayoutNode::height(&self.layout, self, None),
-                    )
+        let command = match message {
+            Message::QueryChanged(new_query) => self.edit_query(|menu| {
+                menu.query.set_text(new_query);
+                Command::none()
+            }),
+            Message::CharacterReceived(c) => self.type_char(c),
+            // The query is a single line
+            Message::Pasted(text) => self.edit_query(|menu| {
+                let text = text.unwrap_or_default().replace(['\r', '\n'], " ");
+                menu.query.insert(&text);
+                Command::none()
+            }),
+            Message::ItemsReceived(new_items) => {
+                let num_items_prev = self.visible_items().len();
+                self.add_items(new_items);
+                self.resize_if_needed(num_items_prev)
+            }
+            Message::CallbackFinished(generation, result) => {
+                // Results for an outdated query are dropped
+                if generation != self.callback_generation {
+                    return Command::none();
+                }
+                self.callback_pending = false;
+                let num_items_prev = self.visible_items().len();
+                let had_error = self.error.is_some();
+                let command = match result {
+                    Ok(new_items) => {
+                        self.error = None;
+                        self.replace_items(new_items);
+                        Command::none()
+                    }
+                    // Keep showing the previous items alongside the error
+                    Err(e) => self.show_error(e),
+                };
+                let resize = self.resize_if_needed(num_items_prev);
+                if had_error != self.error.is_some() {
+                    Command::batch(vec![command, self.resize()])
                 } else {
-                    Command::none()
+                    Command::batch(vec![command, resize])
                 }
             }
-            Message::CursorMoved(direction) => {
-                self.move_cursor(direction);
-                Command::none()
+            Message::Provider(event) => self.handle_provider_event(event),
+            Message::InputClosed(error) => {
+                let command = match error {
+                    Some(e) => self.show_error(e),
+                    None => Command::none(),
+                };
+                self.loading = false;
+                Command::batch(vec![command, self.resize()])
             }
-            Message::CursorSelectionToggled => {
-                self.update_selection(self.index_under_cursor(), SelectionChange::Toggle);
+            Message::KeyPressed(chord) => self.handle_key(chord),
+            Message::Scrolled(delta) => {
+                let rows = self.scrolled_rows(delta);
+                self.scroll(rows);
                 Command::none()
             }
             Message::MouseClicked(index) => {
                 self.update_selection(index, SelectionChange::Toggle);
-                self.submit();
-                window::close()
+                self.submit(Exit::Accept, "")
             }
-            Message::Submitted => {
-                self.update_selection(self.index_under_cursor(), SelectionChange::Select);
-                self.submit();
-                window::close()
+            Message::PreviewFinished(command, result) => {
+                // Errors are shown in the Preview node, like the output would have been
+                let shown = self
+                    .preview
+                    .as_mut()
+                    .is_some_and(|preview| preview.finished(command, result));
+                if shown {
+                    Self::scroll_preview_to_top()
+                } else {
+                    Command::none()
+                }
             }
-            Message::Quit => window::close(),
-        }
+            Message::Executed(result) => {
+                let had_error = self.error.is_some();
+                let command = match result {
+                    Ok(()) => {
+                        self.error = None;
+                        Command::none()
+                    }
+                    Err(e) => self.show_error(e),
+                };
+                if had_error != self.error.is_some() {
+                    Command::batch(vec![command, self.resize()])
+                } else {
+                    command
+                }
+            }
+        };
+        // Almost any message can move the cursor to another item
+        Command::batch(vec![command, self.update_preview()])
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        subscription::events_with(|event, status| match (event, status) {
+        let input = if self.loading {
+            input::subscription(self.parse_options.clone())
+        } else {
+            Subscription::none()
+        };
+        let callback = match &self.callback {
+            Some(callback) if self.callback_pending => {
+                callback.subscription(self.callback_generation, self.query.text())
+            }
+            _ => Subscription::none(),
+        };
+        let provider = match &self.provider {
+            Some(provider) => provider.subscription(),
+            None => Subscription::none(),
+        };
+        let events = subscription::events_with(|event, status| match (event, status) {
             (
                 Event::Keyboard(keyboard::Event::KeyPressed {
                     key_code,
                     modifiers,
                 }),
                 _,
-            ) => match (key_code, modifiers) {
-                (KeyCode::K, keyboard::Modifiers::CTRL) | (KeyCode::Up, _) => {
-                    Some(Message::CursorMoved(CursorMoveDirection::Up))
-                }
-                (KeyCode::J, keyboard::Modifiers::CTRL) | (KeyCode::Down, _) => {
-                    Some(Message::CursorMoved(CursorMoveDirection::Down))
-                }
-                (KeyCode::Tab, _) => Some(Message::CursorSelectionToggled),
-                (KeyCode::Escape, _) | (KeyCode::D, keyboard::Modifiers::CTRL) => {
-                    Some(Message::Quit)
-                }
-                _ => None,
-            },
+            ) => Some(Message::KeyPressed(Chord::new(key_code, modifiers))),
+            (Event::Keyboard(keyboard::Event::CharacterReceived(c)), _) => {
+                Some(Message::CharacterReceived(c))
+            }
+            // The preview scrolls by itself
+            (Event::Mouse(mouse::Event::WheelScrolled { delta }), event::Status::Ignored) => {
+                Some(Message::Scrolled(delta))
+            }
             _ => None,
-        })
+        });
+        let preview = match &self.preview {
+            Some(preview) => preview.subscription(),
+            None => Subscription::none(),
+        };
+        Subscription::batch(vec![input, callback, provider, preview, events])
     }
 }
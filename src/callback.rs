@@ -1,33 +1,128 @@
+use crate::app::Message;
+use crate::error::MenuError;
 use crate::item;
+use iced::{subscription, Subscription};
 use std::io;
-use std::process::Command;
+use std::time::Duration;
+use tokio::process::Command;
 
+#[derive(Clone)]
 pub struct Callback {
     program: String,
     args: Vec<String>,
+    parse_options: item::ParseOptions,
+    debounce: Duration,
 }
 
-const QUERY_VAR_NAME: &str = "$QUERY";
+pub const QUERY_VAR_NAME: &str = "$QUERY";
 
 impl Callback {
-    pub fn new(cli_args: Vec<String>) -> Self {
+    pub fn new(
+        cli_args: Vec<String>,
+        parse_options: item::ParseOptions,
+        debounce: Duration,
+    ) -> Self {
         let program = cli_args
-            .get(0)
+            .first()
             .unwrap_or_else(|| unreachable!("Clap should force at least one argument for callback"))
             .to_string();
         let args = cli_args.iter().skip(1).map(String::from).collect();
-        Self { program, args }
+        Self {
+            program,
+            args,
+            parse_options,
+            debounce,
+        }
     }
 
-    pub fn call(&mut self, query: &str) -> Vec<item::Item> {
+    /// Runs the callback for `query` once the debounce interval has passed.
+    /// Each `generation` is a separate subscription, so starting a new one drops the
+    /// previous one, which kills its process if it is still running.
+    pub fn subscription(&self, generation: usize, query: &str) -> Subscription<Message> {
+        struct CallbackRun;
+
+        let callback = self.clone();
+        subscription::unfold(
+            (std::any::TypeId::of::<CallbackRun>(), generation),
+            Some(query.to_owned()),
+            move |query| {
+                let callback = callback.clone();
+                async move {
+                    match query {
+                        Some(query) => {
+                            tokio::time::sleep(callback.debounce).await;
+                            let result = callback.call(&query).await;
+                            (Message::CallbackFinished(generation, result), None)
+                        }
+                        None => iced::futures::future::pending().await,
+                    }
+                }
+            },
+        )
+    }
+
+    async fn call(&self, query: &str) -> Result<Vec<item::Item>, MenuError> {
         let output = Command::new(&self.program)
             .args(
                 self.args
                     .iter()
                     .map(|a| if a == QUERY_VAR_NAME { query } else { a }),
             )
+            .kill_on_drop(true)
             .output()
-            .expect("Error running callback");
-        item::parse_items(io::Cursor::new(output.stdout)).expect("Problem parsing callback output")
+            .await
+            .map_err(|e| MenuError::CallbackSpawn {
+                program: self.program.clone(),
+                message: e.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(MenuError::CallbackFailed {
+                program: self.program.clone(),
+                status: output.status.to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        item::parse_items(io::Cursor::new(output.stdout), &self.parse_options).map_err(|e| {
+            MenuError::CallbackOutput {
+                program: self.program.clone(),
+                message: e.to_string(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::InputFormat;
+
+    fn shell(script: &str) -> Callback {
+        let options = item::ParseOptions {
+            format: InputFormat::Lines,
+            separator: b'\n',
+            delimiter: None,
+            with_nth: None,
+            markup: None,
+        };
+        let command = ["sh", "-c", script, "sh", QUERY_VAR_NAME].map(String::from);
+        Callback::new(command.to_vec(), options, Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn passes_the_query() {
+        let items = shell("echo \"$1\"; echo b").call("a").await.unwrap();
+        let keys: Vec<_> = items.iter().map(|item| item.data.key.as_str()).collect();
+        assert_eq!(keys, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn reports_failures() {
+        let error = shell("exit 3").call("").await.unwrap_err();
+        assert_eq!(error.to_string(), "`sh` failed (exit status: 3)");
+        let error = shell("echo a; echo oops >&2; exit 1")
+            .call("")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "`sh` failed (exit status: 1): oops");
     }
 }
@@ -1,5 +1,12 @@
 use clap::{Parser, ValueEnum, ValueHint};
+use regex::Regex;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::columns::{self, ColumnRanges};
+use crate::menu::MenuOptions;
+use crate::output::OutputFormat;
+use crate::search::SearchField;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum CaseSensitivity {
@@ -11,8 +18,67 @@ pub enum CaseSensitivity {
     Ignore,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum MatchingAlgorithm {
+    /// Fuzzy matching, as in skim and fzf
+    Fuzzy,
+    /// Items must be equal to the query
+    Exact,
+    /// Items must start with the query
+    Prefix,
+    /// Items must end with the query. Only used for `suffix$` terms of the extended syntax.
+    #[value(skip)]
+    Suffix,
+    /// Items must contain the query
+    Substring,
+    /// Items must contain a word that starts with the query
+    WordPrefix,
+    /// The query is a regular expression
+    Regex,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum InputFormat {
+    /// Each line is the key of an item
+    Lines,
+    /// Each line is split at the first delimiter into a key and a value (TSV by default)
+    Delimited,
+    /// Each line is a CSV record with a key and an optional value
+    Csv,
+    /// A JSON array of item objects (or plain strings)
+    Json,
+    /// One JSON item object (or plain string) per line
+    Ndjson,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SelectionOrder {
+    /// Items are output in the order they were selected
+    Selection,
+    /// Items are output in the order they were read
+    Input,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum CustomPolicy {
+    /// Only items can be submitted
+    Never,
+    /// The query can be submitted with `accept-query` (Shift+Enter)
+    Explicit,
+    /// As with `explicit`, and Enter also submits the query when no item matches
+    NoMatch,
+}
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0       An item was submitted
+  1       The menu was closed without choosing anything
+  2       The options or the config are invalid, or an error happened while the menu
+          was open with the `fatal_errors` setting
+  3       The query was submitted instead of an item
+  10-28   An item was submitted with `custom-1` to `custom-19`";
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 pub struct CliArgs {
     /// The prompt to be displayed
     #[arg(short, long, default_value_t = String::from(""))]
@@ -26,14 +92,63 @@ pub struct CliArgs {
     #[arg(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
     pub file: Option<PathBuf>,
 
+    /// How to parse the items
+    #[arg(short, long, value_enum, default_value_t = InputFormat::Lines)]
+    pub input_format: InputFormat,
+
+    /// Items are separated by NUL characters instead of newlines
+    #[arg(short = '0', long)]
+    pub read0: bool,
+
+    /// The field delimiter for delimited and CSV input [default: tab for delimited, comma for CSV]
+    #[arg(short, long)]
+    pub delimiter: Option<char>,
+
     /// Read a theme from a file
     #[arg(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
     pub config: Option<PathBuf>,
 
+    /// How to match items against the query [default: fuzzy]
+    /// Unless this is `regex`, queries also support fzf's extended search syntax:
+    /// `^prefix`, `suffix$`, `'substring`, `!negation` and `a | b` alternatives
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    pub matching: Option<MatchingAlgorithm>,
+
+    /// A field to search, with an optional weight for its scores. Can be given multiple times.
+    /// Fields are `key`, `value`, `description`, `icon`, `tags`, `group`, `class`, or
+    /// any extra field of JSON items. [default: key]
+    #[arg(long = "search-field", value_name = "FIELD[:WEIGHT]", value_parser = SearchField::parse, verbatim_doc_comment)]
+    pub search_fields: Vec<SearchField>,
+
+    /// Only search these columns of each key, e.g. `1,3..,-1`
+    /// Columns are split by the delimiter, or by whitespace if there is none. They are
+    /// columns of the whole key, even with `--with-nth`.
+    #[arg(long, value_name = "COLUMNS", value_parser = columns::parse_ranges, verbatim_doc_comment)]
+    pub nth: Option<ColumnRanges>,
+
+    /// Only display these columns of each key, while still outputting all of it.
+    /// Without `--nth`, only the displayed columns are searched.
+    #[arg(long, value_name = "COLUMNS", value_parser = columns::parse_ranges, verbatim_doc_comment)]
+    pub with_nth: Option<ColumnRanges>,
+
     /// How to treat case-sensitivity
     #[arg(long, value_enum, default_value_t = CaseSensitivity::Smart)]
     pub case: CaseSensitivity,
 
+    /// Ignore diacritics and compatibility differences when matching, so `cafe` matches `Café`
+    #[arg(long)]
+    pub normalize: bool,
+
+    /// Show the colors and weights of ANSI escape sequences in keys, which are stripped
+    /// for matching and output
+    #[arg(long, conflicts_with = "markup", verbatim_doc_comment)]
+    pub ansi: bool,
+
+    /// Show markup in keys, like `<b>bold</b>`, `<i>italic</i>` or
+    /// `<span color="red">red</span>`. Tags are stripped for matching and output.
+    #[arg(long, verbatim_doc_comment)]
+    pub markup: bool,
+
     /// The maximum number of items that can be selected
     #[arg(short, long, default_value_t = 1)]
     pub max: usize,
@@ -42,9 +157,114 @@ pub struct CliArgs {
     #[arg(long, default_value_t = 10)]
     pub max_visible: usize,
 
+    /// How to output each submitted item: `key`, `value`, `nul`, `index`, `json`, `csv`,
+    /// or a template like `{index}\t{key}\t{value}` where `{field}` is replaced by any
+    /// field of the item. `value` falls back to the key when an item has no value, and
+    /// `nul` is `value` separated by NUL characters instead of newlines.
+    #[arg(long, value_name = "FORMAT", default_value = "value", value_parser = OutputFormat::parse, verbatim_doc_comment)]
+    pub output_format: OutputFormat,
+
+    /// The order in which multiple selected items are output
+    #[arg(long, value_enum, default_value_t = SelectionOrder::Input)]
+    pub selection_order: SelectionOrder,
+
+    /// Print the query on the first line of output
+    #[arg(long)]
+    pub print_query: bool,
+
+    /// Print the keys that closed the menu, like `enter` or `alt+1`, before the items.
+    /// It is empty when an item is clicked.
+    #[arg(long, verbatim_doc_comment)]
+    pub print_key: bool,
+
+    /// Use vim-style modes: Escape switches to normal mode, where j/k, gg/G and Ctrl-d/Ctrl-u
+    /// move, x toggles the selection and / goes back to typing
+    #[arg(long, verbatim_doc_comment)]
+    pub vim: bool,
+
+    /// When the query itself can be submitted instead of an item
+    #[arg(long, value_enum, default_value_t = CustomPolicy::Explicit)]
+    pub allow_custom: CustomPolicy,
+
+    /// Only allow submitting a query that matches this regular expression. Otherwise the
+    /// Query node is shown with its `:invalid` style until the query is changed.
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new, verbatim_doc_comment)]
+    pub validate: Option<Regex>,
+
+    /// Print the items matching QUERY, best first, instead of opening the menu.
+    /// Exits with 1 if nothing matches.
+    #[arg(long, value_name = "QUERY", conflicts_with_all = ["callback", "provider"], verbatim_doc_comment)]
+    pub filter: Option<String>,
+
+    /// With `--filter`, print the score and the matched positions of each item before it,
+    /// like `42<TAB>key:0,2,3<TAB>item`. They are fields of `json` output instead.
+    #[arg(long, requires = "filter", verbatim_doc_comment)]
+    pub scores: bool,
+
+    /// Render the menu to a PNG image instead of opening a window, for working on themes.
+    /// This needs a graphics adapter, but a software one like lavapipe or llvmpipe works.
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = ["callback", "provider", "filter"], verbatim_doc_comment)]
+    pub render_to_png: Option<PathBuf>,
+
+    /// How long to wait after the query changes before running the callback, in milliseconds
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 50)]
+    pub debounce: u64,
+
+    /// Run COMMAND once as a long-lived provider that exchanges JSON messages over
+    /// stdin and stdout, instead of running it for every query
+    #[arg(long)]
+    pub provider: bool,
+
     /// Execute an external command to populate items whenever the query is changed
     /// String args with the value $QUERY will be set to the current query before
-    /// each execution.
+    /// each execution. A command that exits unsuccessfully is shown as an error,
+    /// so wrap commands like `grep` that exit with 1 when nothing matches:
+    /// `sh -c 'grep ... || [ $? = 1 ]'`
     #[arg(last = true, value_name = "COMMAND", verbatim_doc_comment, num_args = 1..)]
     pub callback: Option<Vec<String>>,
 }
+
+impl From<CliArgs> for MenuOptions {
+    fn from(args: CliArgs) -> Self {
+        Self {
+            prompt: args.prompt,
+            query: args.query,
+            file: args.file,
+            input_format: args.input_format,
+            read0: args.read0,
+            delimiter: args.delimiter,
+            config: args.config,
+            matching: args.matching,
+            search_fields: args.search_fields,
+            nth: args.nth,
+            with_nth: args.with_nth,
+            case: args.case,
+            normalize: args.normalize,
+            ansi: args.ansi,
+            markup: args.markup,
+            max: args.max,
+            max_visible: args.max_visible,
+            selection_order: args.selection_order,
+            vim: args.vim,
+            allow_custom: args.allow_custom,
+            validate: args.validate,
+            debounce: Duration::from_millis(args.debounce),
+            provider: args.provider,
+            callback: args.callback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_the_menu_options() {
+        let options = MenuOptions::from(CliArgs::parse_from(["icedmenu"]));
+        assert_eq!(
+            format!("{options:?}"),
+            format!("{:?}", MenuOptions::default())
+        );
+    }
+}
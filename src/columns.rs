@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+/// A 1-based, inclusive range of columns, as in fzf's field index expressions.
+/// Negative indices count from the last column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnRange {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl ColumnRange {
+    /// Resolves the range into 0-based column indices, given the number of columns
+    fn indices(&self, n_columns: usize) -> Range<usize> {
+        let resolve = |i: i64| {
+            if i < 0 {
+                n_columns as i64 + i
+            } else {
+                i - 1
+            }
+        };
+        let start = self
+            .start
+            .map(resolve)
+            .unwrap_or(0)
+            .clamp(0, n_columns as i64) as usize;
+        let end = self
+            .end
+            .map(|i| resolve(i) + 1)
+            .unwrap_or(n_columns as i64)
+            .clamp(0, n_columns as i64) as usize;
+        start..end.max(start)
+    }
+}
+
+/// A list of column ranges, like fzf's `--nth` and `--with-nth`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnRanges(pub Vec<ColumnRange>);
+
+/// Parses a comma-separated list of column ranges like `1,3..,-1`
+pub fn parse_ranges(s: &str) -> Result<ColumnRanges, String> {
+    let parse_index = |i: &str| match i.parse::<i64>() {
+        Ok(0) | Err(_) => Err(format!(
+            "`{i}` is not a valid column index (columns are numbered from 1, or from -1 at the end)"
+        )),
+        Ok(i) => Ok(i),
+    };
+    s.split(',')
+        .map(|range| match range.split_once("..") {
+            Some((start, end)) => Ok(ColumnRange {
+                start: (!start.is_empty())
+                    .then(|| parse_index(start))
+                    .transpose()?,
+                end: (!end.is_empty()).then(|| parse_index(end)).transpose()?,
+            }),
+            None => {
+                let i = parse_index(range)?;
+                Ok(ColumnRange {
+                    start: Some(i),
+                    end: Some(i),
+                })
+            }
+        })
+        .collect::<Result<_, _>>()
+        .map(ColumnRanges)
+}
+
+/// Splits `text` into the char ranges of its columns.
+/// Without a delimiter, columns are separated by runs of whitespace.
+fn columns(chars: &[char], delimiter: Option<char>) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        match delimiter {
+            Some(d) if c == d => {
+                result.push(start..i);
+                start = i + 1;
+            }
+            None if c.is_whitespace() => {
+                if start < i {
+                    result.push(start..i);
+                }
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if delimiter.is_some() || start < chars.len() {
+        result.push(start..chars.len());
+    }
+    result
+}
+
+/// A view of some of the columns of a string, which can map positions back into the original
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnView {
+    pub text: String,
+    /// The position in the original string of each char in `text`
+    positions: Vec<Option<usize>>,
+}
+
+impl ColumnView {
+    pub fn new(text: &str, delimiter: Option<char>, ranges: &ColumnRanges) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let columns = columns(&chars, delimiter);
+        let separator = delimiter.unwrap_or(' ');
+        let mut result = Self {
+            text: String::new(),
+            positions: Vec::new(),
+        };
+        for range in ranges.0.iter() {
+            for column in &columns[range.indices(columns.len())] {
+                if !result.positions.is_empty() {
+                    result.text.push(separator);
+                    result.positions.push(None);
+                }
+                for i in column.clone() {
+                    result.text.push(chars[i]);
+                    result.positions.push(Some(i));
+                }
+            }
+        }
+        result
+    }
+
+    /// Maps char indices in the view back to char indices in the original string
+    pub fn original_indices(&self, indices: &[usize]) -> Vec<usize> {
+        indices
+            .iter()
+            .filter_map(|&i| *self.positions.get(i)?)
+            .collect()
+    }
+
+    /// Maps char indices in the original string to char indices in the view, dropping
+    /// those of columns that aren't in it
+    pub fn view_indices(&self, indices: &[usize]) -> Vec<usize> {
+        self.positions
+            .iter()
+            .enumerate()
+            .filter(|(_, position)| position.is_some_and(|p| indices.contains(&p)))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
@@ -1,8 +1,17 @@
+use crate::Reflective;
+use clap::ValueEnum;
+use kdl::{KdlEntry, KdlNode};
 use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
+use crate::cli::MatchingAlgorithm;
+use crate::layout::style::{bool_value, float_attr, string_value};
+use crate::search::SearchField;
+
 pub const LAYOUT_KEY: &str = "Layout";
 pub const STYLES_KEY: &str = "Styles";
+pub const SETTINGS_KEY: &str = "Settings";
+pub const KEYBINDINGS_KEY: &str = "Keybindings";
 
 #[derive(Error, Diagnostic, Debug)]
 pub enum ConfigError {
@@ -71,6 +80,24 @@ pub enum ConfigError {
         help: String,
     },
 
+    #[error("Invalid setting")]
+    #[diagnostic()]
+    InvalidSetting {
+        #[label("Setting")]
+        setting_src: SourceSpan,
+        #[help]
+        help: String,
+    },
+
+    #[error("Invalid keybinding")]
+    #[diagnostic()]
+    InvalidKeybinding {
+        #[label("Key chord")]
+        chord_src: SourceSpan,
+        #[help]
+        help: String,
+    },
+
     #[error("Font not found")]
     #[diagnostic()]
     FontNotFound {
@@ -80,3 +107,79 @@ pub enum ConfigError {
         help: String,
     },
 }
+
+#[derive(Debug, Default, Clone, Reflective)]
+pub struct Settings {
+    /// Exit when an error happens instead of showing it in the menu
+    pub fatal_errors: bool,
+    /// How to match items against the query, unless overridden by `--matching`
+    pub matching: Option<MatchingAlgorithm>,
+    /// An item field to search, like `search_field "description" weight=0.5`.
+    /// Can be repeated, and is overridden by `--search-field`.
+    pub search_field: Vec<SearchField>,
+    /// Ignore diacritics when matching, as with `--normalize`
+    pub normalize: bool,
+    /// Escape switches to vim-style normal mode instead of quitting, as with `--vim`
+    pub vim_mode: bool,
+}
+
+pub fn parse_settings(node: &KdlNode) -> Result<Settings, ConfigError> {
+    let mut result = Settings::default();
+    let setting_definitions = node.children().map(|d| d.nodes()).unwrap_or_default();
+
+    for child in setting_definitions.iter() {
+        let name = child.name().value();
+        let value_def = child.get(0).ok_or_else(|| ConfigError::MissingArgument {
+            node_src: *child.span(),
+            help: format!("Provide a value for this setting: `{name} <value>`"),
+        })?;
+        match name {
+            "fatal_errors" => result.fatal_errors = bool_value(child, value_def)?,
+            "matching" => result.matching = Some(enum_value(child, value_def)?),
+            "normalize" => result.normalize = bool_value(child, value_def)?,
+            "vim_mode" => result.vim_mode = bool_value(child, value_def)?,
+            "search_field" => {
+                let mut field = SearchField::new(string_value(child, value_def)?);
+                if let Some(weight_def) = child.get("weight") {
+                    field.weight = float_attr(child, weight_def)?;
+                }
+                result.search_field.push(field);
+            }
+            _ => {
+                return Err(ConfigError::InvalidSetting {
+                    setting_src: *child.span(),
+                    help: format!(
+                        "Settings can be one of:\n{}",
+                        Settings::reflect_attr_names()
+                            .iter()
+                            .map(|n| format!("\t{n}"))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ),
+                })
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Parses a string setting into one of the values of a CLI enum
+fn enum_value<T: ValueEnum>(
+    setting_definition: &KdlNode,
+    value_definition: &KdlEntry,
+) -> Result<T, ConfigError> {
+    let value = string_value(setting_definition, value_definition)?;
+    T::from_str(value, true).map_err(|_| ConfigError::InvalidValue {
+        attr_src: *setting_definition.name().span(),
+        value_src: *value_definition.span(),
+        help: format!(
+            "`{}` can be one of: {}",
+            setting_definition.name().value(),
+            T::value_variants()
+                .iter()
+                .filter_map(|v| Some(v.to_possible_value()?.get_name().to_owned()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    })
+}
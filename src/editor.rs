@@ -0,0 +1,479 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The most text that is remembered for yanking
+const KILL_RING_SIZE: usize = 16;
+
+/// What the last change to the query was, so that consecutive edits can be grouped together
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+enum Edit {
+    #[default]
+    None,
+    Typing,
+    Delete,
+    Kill,
+    /// Text from this entry of the kill ring was inserted at this range
+    Yank(usize, Range<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    text: String,
+    cursor: usize,
+}
+
+/// The text of the query and its cursor, with readline-style editing commands. The query's
+/// text input only shows them. Positions are counted in graphemes, like the cursor of iced's
+/// `text_input`.
+#[derive(Debug, Clone, Default)]
+pub struct QueryEditor {
+    text: String,
+    cursor: usize,
+    kill_ring: Vec<String>,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    last_edit: Edit,
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+impl QueryEditor {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            cursor: text.graphemes(true).count(),
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn graphemes(&self) -> Vec<&str> {
+        self.text.graphemes(true).collect()
+    }
+
+    fn byte_offset(&self, position: usize) -> usize {
+        self.text
+            .grapheme_indices(true)
+            .nth(position)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            cursor: self.cursor,
+        }
+    }
+
+    /// Remembers the current state before it is changed
+    fn push_undo(&mut self) {
+        self.undo_stack.push(self.snapshot());
+        self.redo_stack.clear();
+    }
+
+    /// Inserts typed or pasted text at the cursor. Typing a word is undone all at once.
+    pub fn insert(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let typed = text.graphemes(true).count() == 1 && !is_whitespace(text);
+        if !(typed && self.last_edit == Edit::Typing) {
+            self.push_undo();
+        }
+        let offset = self.byte_offset(self.cursor);
+        self.text.insert_str(offset, text);
+        // A combining character joins the grapheme before it
+        self.cursor = self.text[..offset + text.len()].graphemes(true).count();
+        self.last_edit = if typed { Edit::Typing } else { Edit::None };
+    }
+
+    /// Replaces the whole query, leaving the cursor at its end
+    pub fn set_text(&mut self, text: String) {
+        if text != self.text {
+            self.push_undo();
+        }
+        self.cursor = text.graphemes(true).count();
+        self.text = text;
+        self.last_edit = Edit::None;
+    }
+
+    /// Removes a range of graphemes without saving them. Consecutive deletions are undone
+    /// all at once.
+    fn delete(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        if self.last_edit != Edit::Delete {
+            self.push_undo();
+        }
+        let bytes = self.byte_offset(range.start)..self.byte_offset(range.end);
+        self.text.replace_range(bytes, "");
+        self.cursor = range.start;
+        self.last_edit = Edit::Delete;
+    }
+
+    pub fn backward_delete_char(&mut self) {
+        self.delete(self.cursor.saturating_sub(1)..self.cursor);
+    }
+
+    pub fn delete_char(&mut self) {
+        self.delete(self.cursor..(self.cursor + 1).min(self.graphemes().len()));
+    }
+
+    pub fn clear(&mut self) {
+        if !self.text.is_empty() {
+            self.push_undo();
+        }
+        self.text.clear();
+        self.cursor = 0;
+        self.last_edit = Edit::None;
+    }
+
+    fn move_to(&mut self, position: usize) {
+        self.cursor = position;
+        self.last_edit = Edit::None;
+    }
+
+    pub fn beginning_of_line(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn end_of_line(&mut self) {
+        self.move_to(self.graphemes().len());
+    }
+
+    pub fn backward_char(&mut self) {
+        self.move_to(self.cursor.saturating_sub(1));
+    }
+
+    pub fn forward_char(&mut self) {
+        self.move_to((self.cursor + 1).min(self.graphemes().len()));
+    }
+
+    /// The start of the word before the cursor, where words are made of `in_word` graphemes
+    fn word_start(&self, in_word: fn(&str) -> bool) -> usize {
+        let graphemes = self.graphemes();
+        let mut i = self.cursor;
+        while i > 0 && !in_word(graphemes[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && in_word(graphemes[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// The end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let graphemes = self.graphemes();
+        let mut i = self.cursor;
+        while i < graphemes.len() && !is_word(graphemes[i]) {
+            i += 1;
+        }
+        while i < graphemes.len() && is_word(graphemes[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    pub fn backward_word(&mut self) {
+        self.move_to(self.word_start(is_word));
+    }
+
+    pub fn forward_word(&mut self) {
+        self.move_to(self.word_end());
+    }
+
+    /// Removes a range of graphemes into the kill ring. Consecutive kills are joined together.
+    fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.push_undo();
+        let bytes = self.byte_offset(range.start)..self.byte_offset(range.end);
+        let killed: String = self.text.drain(bytes).collect();
+        let backward = range.start < self.cursor;
+        match self.kill_ring.last_mut() {
+            Some(last) if self.last_edit == Edit::Kill && backward => last.insert_str(0, &killed),
+            Some(last) if self.last_edit == Edit::Kill => last.push_str(&killed),
+            _ => {
+                if self.kill_ring.len() == KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+                self.kill_ring.push(killed);
+            }
+        }
+        self.cursor = range.start;
+        self.last_edit = Edit::Kill;
+    }
+
+    /// Kills back to the previous whitespace, like Ctrl-W in a shell
+    pub fn unix_word_rubout(&mut self) {
+        self.kill(self.word_start(|g| !is_whitespace(g))..self.cursor);
+    }
+
+    pub fn backward_kill_word(&mut self) {
+        self.kill(self.word_start(is_word)..self.cursor);
+    }
+
+    pub fn kill_word(&mut self) {
+        self.kill(self.cursor..self.word_end());
+    }
+
+    pub fn unix_line_discard(&mut self) {
+        self.kill(0..self.cursor);
+    }
+
+    pub fn kill_line(&mut self) {
+        self.kill(self.cursor..self.graphemes().len());
+    }
+
+    fn insert_yank(&mut self, index: usize) {
+        let yanked = self.kill_ring[index].clone();
+        let offset = self.byte_offset(self.cursor);
+        self.text.insert_str(offset, &yanked);
+        let start = self.cursor;
+        self.cursor += yanked.graphemes(true).count();
+        self.last_edit = Edit::Yank(index, start..self.cursor);
+    }
+
+    /// Inserts the most recently killed text
+    pub fn yank(&mut self) {
+        if self.kill_ring.is_empty() {
+            return;
+        }
+        self.push_undo();
+        self.insert_yank(self.kill_ring.len() - 1);
+    }
+
+    /// Replaces the text that was just yanked with the previous entry of the kill ring
+    pub fn yank_pop(&mut self) {
+        let Edit::Yank(index, range) = self.last_edit.clone() else {
+            return;
+        };
+        self.push_undo();
+        let bytes = self.byte_offset(range.start)..self.byte_offset(range.end);
+        self.text.replace_range(bytes, "");
+        self.cursor = range.start;
+        let previous = index.checked_sub(1).unwrap_or(self.kill_ring.len() - 1);
+        self.insert_yank(previous);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            self.redo_stack.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            self.undo_stack.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.cursor = snapshot.cursor;
+        self.last_edit = Edit::None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An editor with the cursor where `|` is in `text`
+    fn editor(text: &str) -> QueryEditor {
+        let (before, after) = text.split_once('|').unwrap();
+        let mut editor = QueryEditor::new(&format!("{before}{after}"));
+        editor.cursor = before.graphemes(true).count();
+        editor
+    }
+
+    /// The text with `|` at the cursor
+    fn shown(editor: &QueryEditor) -> String {
+        let offset = editor.byte_offset(editor.cursor);
+        format!("{}|{}", &editor.text[..offset], &editor.text[offset..])
+    }
+
+    fn type_text(editor: &mut QueryEditor, text: &str) {
+        for grapheme in text.graphemes(true) {
+            editor.insert(grapheme);
+        }
+    }
+
+    #[test]
+    fn insertion() {
+        let mut query = editor("ac|");
+        query.backward_char();
+        type_text(&mut query, "b");
+        assert_eq!(shown(&query), "ab|c");
+        query.insert("e\u{301}");
+        assert_eq!(shown(&query), "abe\u{301}|c");
+        query.backward_char();
+        assert_eq!(shown(&query), "ab|e\u{301}c");
+        query.end_of_line();
+        query.insert("\u{301}");
+        assert_eq!(shown(&query), "abe\u{301}c\u{301}|");
+        query.beginning_of_line();
+        assert_eq!(shown(&query), "|abe\u{301}c\u{301}");
+    }
+
+    #[test]
+    fn deletion() {
+        let mut query = editor("ab|cd");
+        query.backward_delete_char();
+        assert_eq!(shown(&query), "a|cd");
+        query.delete_char();
+        assert_eq!(shown(&query), "a|d");
+        query.end_of_line();
+        query.delete_char();
+        assert_eq!(shown(&query), "ad|");
+        query.beginning_of_line();
+        query.backward_delete_char();
+        assert_eq!(shown(&query), "|ad");
+        // Deleted text isn't killed
+        query.yank();
+        assert_eq!(shown(&query), "|ad");
+    }
+
+    #[test]
+    fn word_motion() {
+        let mut query = editor("foo-bar  baz|");
+        query.backward_word();
+        assert_eq!(shown(&query), "foo-bar  |baz");
+        query.backward_word();
+        assert_eq!(shown(&query), "foo-|bar  baz");
+        query.backward_word();
+        assert_eq!(shown(&query), "|foo-bar  baz");
+        query.backward_word();
+        assert_eq!(shown(&query), "|foo-bar  baz");
+        query.forward_word();
+        assert_eq!(shown(&query), "foo|-bar  baz");
+        query.forward_word();
+        assert_eq!(shown(&query), "foo-bar|  baz");
+        query.forward_word();
+        query.forward_word();
+        assert_eq!(shown(&query), "foo-bar  baz|");
+    }
+
+    #[test]
+    fn word_kills() {
+        let mut query = editor("foo-bar baz|");
+        query.unix_word_rubout();
+        assert_eq!(shown(&query), "foo-bar |");
+        query.backward_kill_word();
+        assert_eq!(shown(&query), "foo-|");
+        query.beginning_of_line();
+        query.kill_word();
+        assert_eq!(shown(&query), "|-");
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut query = editor("one two| three");
+        query.kill_line();
+        // Consecutive kills are yanked together
+        query.backward_kill_word();
+        query.backward_kill_word();
+        assert_eq!(shown(&query), "|");
+        query.yank();
+        assert_eq!(shown(&query), "one two three|");
+
+        let mut query = editor("one two three|");
+        query.backward_kill_word();
+        query.backward_delete_char();
+        query.backward_kill_word();
+        assert_eq!(shown(&query), "one |");
+        query.yank();
+        assert_eq!(shown(&query), "one two|");
+        // Yank-pop cycles through older kills
+        query.yank_pop();
+        assert_eq!(shown(&query), "one three|");
+        query.yank_pop();
+        assert_eq!(shown(&query), "one two|");
+        // It only follows a yank
+        query.backward_char();
+        query.yank_pop();
+        assert_eq!(shown(&query), "one tw|o");
+    }
+
+    #[test]
+    fn kill_ring_size() {
+        let mut query = editor("|");
+        for i in 0..=KILL_RING_SIZE {
+            query.insert(&i.to_string());
+            query.unix_line_discard();
+            // Kills after other edits aren't joined
+            query.insert(" ");
+            query.backward_delete_char();
+        }
+        assert_eq!(query.kill_ring.len(), KILL_RING_SIZE);
+        query.yank();
+        assert_eq!(query.text(), KILL_RING_SIZE.to_string());
+        query.yank_pop();
+        assert_eq!(query.text(), (KILL_RING_SIZE - 1).to_string());
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut query = editor("|");
+        type_text(&mut query, "foo bar");
+        query.backward_delete_char();
+        query.backward_delete_char();
+        assert_eq!(shown(&query), "foo b|");
+        // Deletions and typed words are undone together
+        query.undo();
+        assert_eq!(shown(&query), "foo bar|");
+        query.undo();
+        assert_eq!(shown(&query), "foo |");
+        query.undo();
+        assert_eq!(shown(&query), "foo|");
+        query.undo();
+        assert_eq!(shown(&query), "|");
+        query.undo();
+        assert_eq!(shown(&query), "|");
+        query.redo();
+        query.redo();
+        assert_eq!(shown(&query), "foo |");
+        // A new edit drops what could be redone
+        query.insert("baz");
+        query.redo();
+        assert_eq!(shown(&query), "foo baz|");
+        query.undo();
+        assert_eq!(shown(&query), "foo |");
+    }
+
+    #[test]
+    fn undo_kills_and_yanks() {
+        let mut query = editor("foo bar|");
+        query.backward_kill_word();
+        query.yank();
+        query.yank();
+        assert_eq!(shown(&query), "foo barbar|");
+        query.undo();
+        assert_eq!(shown(&query), "foo bar|");
+        query.undo();
+        assert_eq!(shown(&query), "foo |");
+        query.undo();
+        assert_eq!(shown(&query), "foo bar|");
+        query.clear();
+        query.undo();
+        assert_eq!(shown(&query), "foo bar|");
+    }
+}
@@ -0,0 +1,36 @@
+use thiserror::Error;
+
+/// Errors that can happen while the menu is running
+#[derive(Error, Debug, Clone)]
+pub enum MenuError {
+    #[error("Could not read items: {0}")]
+    Input(String),
+
+    #[error("Could not run `{program}`: {message}")]
+    CallbackSpawn { program: String, message: String },
+
+    #[error("`{program}` failed ({status}){}", with_colon(.stderr))]
+    CallbackFailed {
+        program: String,
+        status: String,
+        stderr: String,
+    },
+
+    #[error("Could not parse the output of `{program}`: {message}")]
+    CallbackOutput { program: String, message: String },
+
+    #[error("Could not run the preview `{command}`: {message}")]
+    Preview { command: String, message: String },
+
+    #[error("Provider `{program}`: {message}")]
+    Provider { program: String, message: String },
+}
+
+/// The output of a failed command after a colon, if it printed anything
+fn with_colon(output: &str) -> String {
+    if output.is_empty() {
+        String::new()
+    } else {
+        format!(": {output}")
+    }
+}
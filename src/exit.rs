@@ -0,0 +1,38 @@
+use std::io::{self, Write};
+
+/// How the menu was closed, which scripts can tell apart by the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The selected items were submitted
+    Accept,
+    /// The menu was closed without choosing anything
+    Cancel,
+    /// The options or the config are invalid, or an error happened with `fatal_errors` set
+    Error,
+    /// The query itself was submitted instead of any item
+    AcceptQuery,
+    /// The selected items were submitted with one of the `custom-N` actions
+    Custom(u8),
+}
+
+/// The number of `custom-N` actions, as in rofi's `kb-custom-1..19`
+pub const MAX_CUSTOM: u8 = 19;
+
+impl Exit {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Accept => 0,
+            Self::Cancel => 1,
+            Self::Error => 2,
+            Self::AcceptQuery => 3,
+            // custom-1 exits with 10, like rofi
+            Self::Custom(n) => 9 + *n as i32,
+        }
+    }
+
+    /// Exits the process once everything written to stdout has been flushed
+    pub fn exit(&self) -> ! {
+        let _ = io::stdout().flush();
+        std::process::exit(self.code())
+    }
+}
@@ -0,0 +1,42 @@
+use std::io;
+
+use crate::app::Flags;
+use crate::exit::Exit;
+use crate::item::Item;
+use crate::output::Output;
+use crate::search::{self, Search};
+
+/// Prints the items matching `query`, ranked just like in the menu, without opening a
+/// window. Exits with `Exit::Cancel` if nothing matched.
+pub fn run(mut flags: Flags, query: &str, output: &Output) -> ! {
+    flags.read_stdin();
+    if let Some(e) = &flags.error {
+        eprintln!("{e}");
+        Exit::Error.exit();
+    }
+    let mut items = std::mem::take(&mut flags.items);
+
+    let searcher = flags.searcher();
+    let search = Search::parse(query, searcher.algorithm);
+    searcher.score_items(&search, &mut items);
+    let matches: Vec<&Item> = search::rank_items(&search, &items)
+        .into_iter()
+        .map(|i| &items[i])
+        .collect();
+
+    let records = output.records(&matches);
+    match output.write(&mut io::stdout().lock(), query, "", records) {
+        // Like `icedmenu --filter q | head -1`, which stops reading early
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Could not write the matches: {e}");
+            Exit::Error.exit();
+        }
+        Ok(()) => {}
+    }
+    if matches.is_empty() {
+        Exit::Cancel.exit()
+    } else {
+        Exit::Accept.exit()
+    }
+}
@@ -3,6 +3,7 @@ use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use kdl::{KdlEntry, KdlNode};
 use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 
 use iced::Font;
 
@@ -56,6 +57,50 @@ impl FontLoader {
     }
 }
 
+/// The family used for bold and italic text in the default font
+const DEFAULT_FAMILY: &str = "sans-serif";
+
+type VariantKey = (&'static str, bool, bool);
+
+/// The bold and/or italic version of `font`, or `font` itself if the system doesn't have one.
+/// Variants are loaded the first time they are needed.
+pub fn variant(font: Option<Font>, bold: bool, italic: bool) -> Option<Font> {
+    if !bold && !italic {
+        return font;
+    }
+    static VARIANTS: OnceLock<Mutex<HashMap<VariantKey, Option<Font>>>> = OnceLock::new();
+    let family = match font {
+        Some(Font::External { name, .. }) => name,
+        _ => DEFAULT_FAMILY,
+    };
+    let mut variants = VARIANTS.get_or_init(Default::default).lock().unwrap();
+    let loaded = *variants
+        .entry((family, bold, italic))
+        .or_insert_with(|| load_variant(family, bold, italic));
+    loaded.or(font)
+}
+
+fn load_variant(family: &str, bold: bool, italic: bool) -> Option<Font> {
+    let mut font_props = system_fonts::FontPropertyBuilder::new().family(family);
+    if bold {
+        font_props = font_props.bold();
+    }
+    if italic {
+        font_props = font_props.italic();
+    }
+    let (bytes, _) = system_fonts::get(&font_props.build())?;
+    // Fonts are told apart by their names
+    let name = format!(
+        "{family}{}{}",
+        if bold { " Bold" } else { "" },
+        if italic { " Italic" } else { "" }
+    );
+    Some(Font::External {
+        name: Box::leak(name.into_boxed_str()),
+        bytes: Box::leak(bytes.into_boxed_slice()),
+    })
+}
+
 fn similar_system_fonts(pattern: &str, n: usize) -> Vec<String> {
     let matcher = SkimMatcherV2::default().ignore_case();
     let mut result: Vec<_> = system_fonts::query_all()
@@ -0,0 +1,70 @@
+use std::sync::OnceLock;
+
+use iced::Color;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+use crate::ansi::{self, Line, Span};
+
+/// One of the themes built into syntect, chosen with the `syntax_theme` style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxTheme(&'static str);
+
+impl SyntaxTheme {
+    pub const NAMES: [&'static str; 7] = [
+        "base16-ocean.dark",
+        "base16-eighties.dark",
+        "base16-mocha.dark",
+        "base16-ocean.light",
+        "InspiredGitHub",
+        "Solarized (dark)",
+        "Solarized (light)",
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES.into_iter().find(|n| *n == name).map(Self)
+    }
+}
+
+/// Loading the syntaxes takes a moment, so it only happens once something is highlighted
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    THEME_SET.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Colors `text` as the language with the file `extension`, unless there is no syntax for it
+pub fn highlight(text: &str, extension: &str, theme: SyntaxTheme) -> Option<Vec<Line>> {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set.find_syntax_by_extension(extension)?;
+    let mut highlighter = HighlightLines::new(syntax, &theme_set().themes[theme.0]);
+    text.split_inclusive('\n')
+        .map(|line| {
+            let regions = highlighter.highlight_line(line, syntax_set).ok()?;
+            let mut column = 0;
+            Some(
+                regions
+                    .into_iter()
+                    .map(|(style, text)| Span {
+                        text: ansi::expand_tabs(text, &mut column),
+                        color: Some(Color::from_rgba8(
+                            style.foreground.r,
+                            style.foreground.g,
+                            style.foreground.b,
+                            style.foreground.a as f32 / 255.0,
+                        )),
+                        background: None,
+                        bold: style.font_style.contains(FontStyle::BOLD),
+                        italic: style.font_style.contains(FontStyle::ITALIC),
+                    })
+                    .filter(|span| !span.text.is_empty())
+                    .collect(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The theme that every icon theme falls back to
+const FALLBACK_THEME: &str = "hicolor";
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// A subdirectory of a theme, with the sizes of the icons in it
+#[derive(Debug)]
+struct Directory {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: DirectoryType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl Directory {
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.kind {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirectoryType::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let (min, max) = match self.kind {
+            DirectoryType::Fixed => (self.size, self.size),
+            DirectoryType::Scalable => (self.min_size, self.max_size),
+            DirectoryType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let wanted = size * scale;
+        if wanted < min * self.scale {
+            min * self.scale - wanted
+        } else {
+            wanted.saturating_sub(max * self.scale)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Theme {
+    /// The directories where the theme is installed
+    roots: Vec<PathBuf>,
+    directories: Vec<Directory>,
+    inherits: Vec<String>,
+}
+
+/// `~/.icons`, then `icons` in each XDG data directory, then `/usr/share/pixmaps`
+fn base_dirs() -> &'static [PathBuf] {
+    static BASE_DIRS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    BASE_DIRS.get_or_init(|| {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+        let data_dirs = env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+        home.map(|home| home.join(".icons"))
+            .into_iter()
+            .chain(data_home.map(|dir| dir.join("icons")))
+            .chain(env::split_paths(&data_dirs).map(|dir| dir.join("icons")))
+            .chain([PathBuf::from("/usr/share/pixmaps")])
+            .collect()
+    })
+}
+
+/// Parses the `index.theme` of a theme, which is read from the first directory that has one
+fn load_theme(name: &str) -> Option<Theme> {
+    let roots: Vec<PathBuf> = base_dirs()
+        .iter()
+        .map(|dir| dir.join(name))
+        .filter(|dir| dir.is_dir())
+        .collect();
+    let index = roots
+        .iter()
+        .find_map(|root| std::fs::read_to_string(root.join("index.theme")).ok())?;
+    let sections = parse_ini(&index);
+    let theme_section = sections.get("Icon Theme")?;
+    let list = |key: &str| -> Vec<String> {
+        theme_section
+            .get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut directory_names = list("Directories");
+    directory_names.extend(list("ScaledDirectories"));
+    let directories = directory_names
+        .into_iter()
+        .filter_map(|path| {
+            let section = sections.get(path.as_str())?;
+            let number = |key: &str| section.get(key).and_then(|v| v.trim().parse().ok());
+            let size = number("Size")?;
+            let kind = match section.get("Type").map(|t| t.trim()) {
+                Some("Fixed") => DirectoryType::Fixed,
+                Some("Scalable") => DirectoryType::Scalable,
+                _ => DirectoryType::Threshold,
+            };
+            Some(Directory {
+                size,
+                scale: number("Scale").unwrap_or(1),
+                kind,
+                min_size: number("MinSize").unwrap_or(size),
+                max_size: number("MaxSize").unwrap_or(size),
+                threshold: number("Threshold").unwrap_or(2),
+                path,
+            })
+        })
+        .collect();
+    Some(Theme {
+        roots,
+        directories,
+        inherits: list("Inherits"),
+    })
+}
+
+type IniSections<'a> = HashMap<&'a str, HashMap<&'a str, &'a str>>;
+
+fn parse_ini(source: &str) -> IniSections<'_> {
+    let mut sections: IniSections = HashMap::new();
+    let mut current = None;
+    for line in source.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name);
+            sections.entry(name).or_default();
+        } else if let (Some(section), Some((key, value))) = (current, line.split_once('=')) {
+            if !line.starts_with('#') {
+                sections
+                    .entry(section)
+                    .or_default()
+                    .insert(key.trim(), value.trim());
+            }
+        }
+    }
+    sections
+}
+
+/// Themes are parsed the first time they are used
+fn theme(name: &str) -> Option<Arc<Theme>> {
+    static THEMES: OnceLock<Mutex<HashMap<String, Option<Arc<Theme>>>>> = OnceLock::new();
+    let mut themes = THEMES.get_or_init(Default::default).lock().unwrap();
+    themes
+        .entry(name.to_owned())
+        .or_insert_with(|| load_theme(name).map(Arc::new))
+        .clone()
+}
+
+fn icon_file(dir: &Path, icon: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| dir.join(format!("{icon}.{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// Looks for an icon of exactly the right size in a theme, or else the closest one
+fn lookup_icon(icon: &str, size: u32, scale: u32, theme: &Theme) -> Option<PathBuf> {
+    let files = || {
+        theme.directories.iter().flat_map(move |dir| {
+            theme
+                .roots
+                .iter()
+                .filter_map(move |root| Some((dir, icon_file(&root.join(&dir.path), icon)?)))
+        })
+    };
+    if let Some((_, path)) = files().find(|(dir, _)| dir.matches_size(size, scale)) {
+        return Some(path);
+    }
+    files()
+        .min_by_key(|(dir, _)| dir.size_distance(size, scale))
+        .map(|(_, path)| path)
+}
+
+fn find_in_theme(
+    icon: &str,
+    size: u32,
+    scale: u32,
+    name: &str,
+    visited: &mut HashSet<String>,
+) -> Option<PathBuf> {
+    if !visited.insert(name.to_owned()) {
+        return None;
+    }
+    let theme = theme(name)?;
+    lookup_icon(icon, size, scale, &theme).or_else(|| {
+        theme
+            .inherits
+            .iter()
+            .find_map(|parent| find_in_theme(icon, size, scale, parent, visited))
+    })
+}
+
+fn find_uncached(icon: &str, size: u32, scale: u32, theme: &str) -> Option<PathBuf> {
+    let mut visited = HashSet::new();
+    find_in_theme(icon, size, scale, theme, &mut visited)
+        .or_else(|| find_in_theme(icon, size, scale, FALLBACK_THEME, &mut visited))
+        // Icons that aren't part of any theme
+        .or_else(|| base_dirs().iter().find_map(|dir| icon_file(dir, icon)))
+}
+
+type IconKey = (String, u32, u32, String);
+
+/// Finds the file of an icon following the freedesktop icon theme specification, looking in
+/// `theme`, the themes it inherits from, and then `hicolor`. `icon` can also be a path to a
+/// file. Results are cached, since the menu is redrawn often.
+pub fn find(icon: &str, size: u32, scale: u32, theme: &str) -> Option<PathBuf> {
+    if icon.is_empty() {
+        return None;
+    }
+    if icon.contains('/') {
+        let path = PathBuf::from(icon);
+        return path.is_file().then_some(path);
+    }
+    static ICONS: OnceLock<Mutex<HashMap<IconKey, Option<PathBuf>>>> = OnceLock::new();
+    let key = (icon.to_owned(), size, scale, theme.to_owned());
+    if let Some(path) = ICONS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .get(&key)
+    {
+        return path.clone();
+    }
+    let path = find_uncached(icon, size, scale, theme);
+    ICONS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(key, path.clone());
+    path
+}
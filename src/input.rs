@@ -0,0 +1,56 @@
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use std::io;
+use std::thread;
+
+use crate::app::Message;
+use crate::error::MenuError;
+use crate::item::{self, ParseOptions};
+
+/// Streams items from stdin into the menu in batches, as they become available
+pub fn subscription(options: ParseOptions) -> Subscription<Message> {
+    struct StdinReader;
+
+    subscription::channel(
+        std::any::TypeId::of::<StdinReader>(),
+        16,
+        move |mut output| {
+            let options = options.clone();
+            async move {
+                // Reading stdin blocks, so it happens on its own thread
+                let (sender, mut receiver) = mpsc::unbounded();
+                thread::spawn(move || {
+                    for data in item::read_items(io::stdin(), &options) {
+                        let is_err = data.is_err();
+                        if sender.unbounded_send(data).is_err() || is_err {
+                            break;
+                        }
+                    }
+                });
+
+                let mut error = None;
+                while let Some(first) = receiver.next().await {
+                    // Collect everything that has already arrived into a single batch
+                    let mut batch = Vec::new();
+                    let mut next = Some(first);
+                    while let Some(data) = next {
+                        match data {
+                            Ok(data) => batch.push(data),
+                            Err(e) => error = Some(MenuError::Input(e.to_string())),
+                        }
+                        next = receiver.try_next().ok().flatten();
+                    }
+                    if !batch.is_empty() {
+                        let _ = output.send(Message::ItemsReceived(batch)).await;
+                    }
+                    if error.is_some() {
+                        break;
+                    }
+                }
+                let _ = output.send(Message::InputClosed(error)).await;
+                iced::futures::future::pending().await
+            }
+        },
+    )
+}
@@ -1,19 +1,130 @@
+use std::borrow::Cow;
 use std::cmp::{Ord, Ordering};
-use std::{error::Error, io};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::{error::Error, io, iter};
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, serde::Deserialize, serde::Serialize)]
+use crate::ansi::Span;
+use crate::cli::InputFormat;
+use crate::columns::{ColumnRanges, ColumnView};
+use crate::markup::{self, KeyMarkup};
+use crate::menu::MenuOptions;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct ItemData {
     pub key: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Any other fields provided by structured input
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+    /// The key with the colors and weights of `--ansi` or `--markup`, which are stripped
+    /// from `key`
+    #[serde(skip)]
+    pub styled_key: Option<Vec<Span>>,
+    /// The columns of the key that are shown, with `--with-nth`
+    #[serde(skip)]
+    pub display_key: Option<ColumnView>,
+}
+
+impl ItemData {
+    pub fn new(key: String, value: Option<String>) -> Self {
+        Self {
+            key,
+            value,
+            ..Default::default()
+        }
+    }
+
+    /// Looks up a field by name, including any extra fields
+    pub fn field(&self, name: &str) -> Option<Cow<'_, str>> {
+        match name {
+            "key" => Some(Cow::Borrowed(&self.key)),
+            "value" => self.value.as_deref().map(Cow::Borrowed),
+            "description" => self.description.as_deref().map(Cow::Borrowed),
+            "icon" => self.icon.as_deref().map(Cow::Borrowed),
+            "group" => self.group.as_deref().map(Cow::Borrowed),
+            "class" => self.class.as_deref().map(Cow::Borrowed),
+            "tags" if self.tags.is_empty() => None,
+            "tags" => Some(Cow::Owned(self.tags.join(" "))),
+            _ => match self.extra.get(name)? {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) => Some(Cow::Borrowed(s)),
+                other => Some(Cow::Owned(other.to_string())),
+            },
+        }
+    }
+
+    /// Whether `name` is one of the fields of items, or an extra field of this one
+    pub fn is_field(&self, name: &str) -> bool {
+        matches!(
+            name,
+            "key" | "value" | "description" | "icon" | "tags" | "group" | "class"
+        ) || self.extra.contains_key(name)
+    }
+
+    /// Strips the markup from the key, keeping its styles for display
+    fn with_markup(mut self, markup: KeyMarkup) -> Self {
+        let spans = markup.parse(&self.key);
+        self.key = markup::plain_text(&spans);
+        self.styled_key = Some(spans);
+        self
+    }
+
+    /// Displays only some columns of the key, while still outputting the whole of it.
+    /// Styles of the key are dropped, since they no longer line up with what is shown.
+    fn with_columns(mut self, delimiter: Option<char>, ranges: &ColumnRanges) -> Self {
+        self.display_key = Some(ColumnView::new(&self.key, delimiter, ranges));
+        self.styled_key = None;
+        self
+    }
+}
+
+/// Items in JSON input can be given as plain strings or as objects
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum JsonItem {
+    Key(String),
+    Data(Box<ItemData>),
+}
+
+impl From<JsonItem> for ItemData {
+    fn from(item: JsonItem) -> Self {
+        match item {
+            JsonItem::Key(key) => Self::new(key, None),
+            JsonItem::Data(data) => *data,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CsvRecord {
+    key: String,
+    #[serde(default)]
+    value: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Item {
+    /// The position of the item in the menu's items
     pub index: usize,
+    /// The position of the item in the input, or in the output of the callback or provider
+    /// that returned it. Unlike `index`, it is kept when new results replace the items.
+    pub input_index: usize,
     pub data: ItemData,
     pub score: Option<u32>,
-    pub match_indices: Option<Vec<usize>>,
+    /// The positions of the matched characters in each field that matched the query
+    pub match_indices: HashMap<String, Vec<usize>>,
     pub selected: bool,
 }
 
@@ -21,9 +132,10 @@ impl Item {
     pub fn new(index: usize, data: ItemData) -> Self {
         Self {
             index,
+            input_index: index,
             data,
             score: None,
-            match_indices: None,
+            match_indices: HashMap::new(),
             selected: false,
         }
     }
@@ -49,13 +161,127 @@ impl PartialOrd for Item {
     }
 }
 
-pub fn parse_items(source: impl io::Read) -> Result<Vec<Item>, Box<dyn Error>> {
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(source);
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub format: InputFormat,
+    pub separator: u8,
+    pub delimiter: Option<char>,
+    /// Which columns of each key to display
+    pub with_nth: Option<ColumnRanges>,
+    /// How keys are styled, if they are
+    pub markup: Option<KeyMarkup>,
+}
+
+impl ParseOptions {
+    pub fn new(options: &MenuOptions) -> Self {
+        Self {
+            format: options.input_format,
+            separator: if options.read0 { b'\0' } else { b'\n' },
+            delimiter: options.delimiter,
+            with_nth: options.with_nth.clone(),
+            markup: KeyMarkup::new(options.ansi, options.markup),
+        }
+    }
+}
+
+pub type ParseResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+pub fn parse_items(
+    source: impl io::Read + 'static,
+    options: &ParseOptions,
+) -> ParseResult<Vec<Item>> {
     let mut result = Vec::new();
-    for (i, data) in rdr.deserialize::<ItemData>().enumerate() {
+    for (i, data) in read_items(source, options).enumerate() {
         result.push(Item::new(i, data?));
     }
     Ok(result)
 }
+
+/// Lazily parses items from `source`, so that they can be consumed as they arrive
+pub fn read_items(
+    source: impl io::Read + 'static,
+    options: &ParseOptions,
+) -> Box<dyn Iterator<Item = ParseResult<ItemData>>> {
+    let mut items = read_item_data(source, options);
+    if let Some(markup) = options.markup {
+        items = Box::new(items.map(move |data| Ok(data?.with_markup(markup))));
+    }
+    match options.with_nth.clone() {
+        Some(ranges) => {
+            let delimiter = options.delimiter;
+            Box::new(items.map(move |data| Ok(data?.with_columns(delimiter, &ranges))))
+        }
+        None => items,
+    }
+}
+
+fn read_item_data(
+    source: impl io::Read + 'static,
+    options: &ParseOptions,
+) -> Box<dyn Iterator<Item = ParseResult<ItemData>>> {
+    match options.format {
+        InputFormat::Lines => Box::new(
+            records(source, options.separator).map(|record| Ok(ItemData::new(record?, None))),
+        ),
+        InputFormat::Delimited => {
+            let delimiter = options.delimiter.unwrap_or('\t');
+            Box::new(records(source, options.separator).map(move |record| {
+                let record = record?;
+                Ok(match record.split_once(delimiter) {
+                    Some((key, value)) => ItemData::new(key.to_owned(), Some(value.to_owned())),
+                    None => ItemData::new(record, None),
+                })
+            }))
+        }
+        InputFormat::Csv => read_csv(source, options),
+        InputFormat::Json => match serde_json::from_reader::<_, Vec<JsonItem>>(source) {
+            Ok(items) => Box::new(items.into_iter().map(|item| Ok(item.into()))),
+            Err(e) => Box::new(iter::once(Err(e.into()))),
+        },
+        InputFormat::Ndjson => Box::new(
+            records(source, options.separator)
+                .filter(|record| !matches!(record, Ok(r) if r.trim().is_empty()))
+                .map(|record| Ok(serde_json::from_str::<JsonItem>(&record?)?.into())),
+        ),
+    }
+}
+
+/// Splits the input into records on `separator`, ignoring a trailing separator
+fn records(source: impl io::Read, separator: u8) -> impl Iterator<Item = io::Result<String>> {
+    io::BufReader::new(source)
+        .split(separator)
+        .map(move |bytes| {
+            let mut record = String::from_utf8_lossy(&bytes?).into_owned();
+            if separator == b'\n' && record.ends_with('\r') {
+                record.pop();
+            }
+            Ok(record)
+        })
+}
+
+fn read_csv(
+    source: impl io::Read + 'static,
+    options: &ParseOptions,
+) -> Box<dyn Iterator<Item = ParseResult<ItemData>>> {
+    let delimiter = options.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        return Box::new(iter::once(Err(format!(
+            "CSV delimiter must be an ASCII character, got: {delimiter:?}"
+        )
+        .into())));
+    }
+    let mut builder = csv::ReaderBuilder::new();
+    builder.has_headers(false).delimiter(delimiter as u8);
+    if options.separator != b'\n' {
+        builder.terminator(csv::Terminator::Any(options.separator));
+    }
+    Box::new(
+        builder
+            .from_reader(source)
+            .into_deserialize::<CsvRecord>()
+            .map(|record| {
+                let record = record?;
+                Ok(ItemData::new(record.key, record.value))
+            }),
+    )
+}
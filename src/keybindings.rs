@@ -0,0 +1,654 @@
+use iced::keyboard::{KeyCode, Modifiers};
+use kdl::{KdlEntry, KdlNode};
+use std::collections::HashMap;
+use std::fmt;
+use tokio::process::Command;
+
+use crate::callback::QUERY_VAR_NAME;
+use crate::config::ConfigError;
+use crate::error::MenuError;
+use crate::exit::MAX_CUSTOM;
+
+/// Replaced by the output of the item under the cursor in `execute` commands
+pub const ITEM_VAR_NAME: &str = "$ITEM";
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("a", KeyCode::A),
+    ("b", KeyCode::B),
+    ("c", KeyCode::C),
+    ("d", KeyCode::D),
+    ("e", KeyCode::E),
+    ("f", KeyCode::F),
+    ("g", KeyCode::G),
+    ("h", KeyCode::H),
+    ("i", KeyCode::I),
+    ("j", KeyCode::J),
+    ("k", KeyCode::K),
+    ("l", KeyCode::L),
+    ("m", KeyCode::M),
+    ("n", KeyCode::N),
+    ("o", KeyCode::O),
+    ("p", KeyCode::P),
+    ("q", KeyCode::Q),
+    ("r", KeyCode::R),
+    ("s", KeyCode::S),
+    ("t", KeyCode::T),
+    ("u", KeyCode::U),
+    ("v", KeyCode::V),
+    ("w", KeyCode::W),
+    ("x", KeyCode::X),
+    ("y", KeyCode::Y),
+    ("z", KeyCode::Z),
+    ("0", KeyCode::Key0),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+    ("enter", KeyCode::Enter),
+    ("numpad_enter", KeyCode::NumpadEnter),
+    ("escape", KeyCode::Escape),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Space),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("page_up", KeyCode::PageUp),
+    ("page_down", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("comma", KeyCode::Comma),
+    ("period", KeyCode::Period),
+    ("slash", KeyCode::Slash),
+    ("backslash", KeyCode::Backslash),
+    ("semicolon", KeyCode::Semicolon),
+    ("apostrophe", KeyCode::Apostrophe),
+    ("grave", KeyCode::Grave),
+    ("minus", KeyCode::Minus),
+    ("equals", KeyCode::Equals),
+    ("left_bracket", KeyCode::LBracket),
+    ("right_bracket", KeyCode::RBracket),
+];
+
+const MODIFIER_NAMES: &[(&str, Modifiers)] = &[
+    ("ctrl", Modifiers::CTRL),
+    ("alt", Modifiers::ALT),
+    ("shift", Modifiers::SHIFT),
+    ("super", Modifiers::LOGO),
+];
+
+/// A key pressed together with some modifiers, written like `ctrl+shift+k`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    key: KeyCode,
+    modifiers: Modifiers,
+}
+
+impl Chord {
+    pub fn new(key: KeyCode, modifiers: Modifiers) -> Self {
+        Self { key, modifiers }
+    }
+
+    /// Whether this is just a modifier key being pressed on its own
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.key,
+            KeyCode::LShift
+                | KeyCode::RShift
+                | KeyCode::LControl
+                | KeyCode::RControl
+                | KeyCode::LAlt
+                | KeyCode::RAlt
+                | KeyCode::LWin
+                | KeyCode::RWin
+        )
+    }
+
+    /// Whether Ctrl or Super is held, which keeps the character of the chord from being typed
+    pub fn is_command(&self) -> bool {
+        self.modifiers.intersects(Modifiers::CTRL | Modifiers::LOGO)
+    }
+
+    /// Whether pressing `chord` should trigger a binding for this chord: the same key, with at
+    /// least the same modifiers
+    fn triggered_by(&self, chord: &Chord) -> bool {
+        self.key == chord.key && chord.modifiers.contains(self.modifiers)
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = s.split('+').collect();
+        let key_name = parts.pop()?;
+        let key = KEY_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key_name))?
+            .1;
+        let mut modifiers = Modifiers::empty();
+        for part in parts {
+            modifiers |= MODIFIER_NAMES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(part))?
+                .1;
+        }
+        Some(Self { key, modifiers })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, modifier) in MODIFIER_NAMES {
+            if self.modifiers.contains(*modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match KEY_NAMES.iter().find(|(_, key)| *key == self.key) {
+            Some((name, _)) => write!(f, "{name}"),
+            None => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    Toggle,
+    SelectAll,
+    DeselectAll,
+    /// Outputs the selected items, or the item under the cursor
+    Submit,
+    /// Outputs the query itself instead of any item
+    AcceptQuery,
+    ClearQuery,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardChar,
+    ForwardChar,
+    BackwardWord,
+    ForwardWord,
+    BackwardDeleteChar,
+    DeleteChar,
+    /// Kills back to the previous whitespace
+    UnixWordRubout,
+    BackwardKillWord,
+    KillWord,
+    /// Kills back to the start of the query
+    UnixLineDiscard,
+    /// Kills to the end of the query
+    KillLine,
+    /// Inserts the most recently killed text
+    Yank,
+    /// Replaces the text that was just yanked with older killed text
+    YankPop,
+    Undo,
+    Redo,
+    /// Inserts the text in the clipboard
+    Paste,
+    /// Switches to vim-style normal mode, where keys move around instead of typing
+    NormalMode,
+    InsertMode,
+    Quit,
+    /// Submits like `submit`, but exits with the code `9 + N`, like rofi's `kb-custom-N`
+    Custom(u8),
+    /// Runs a command, replacing `$ITEM` and `$QUERY` in its arguments
+    Execute(Vec<String>),
+}
+
+impl Action {
+    const NAMES: &'static [&'static str] = &[
+        "up",
+        "down",
+        "page-up",
+        "page-down",
+        "first",
+        "last",
+        "toggle",
+        "select-all",
+        "deselect-all",
+        "submit",
+        "accept-query",
+        "clear-query",
+        "beginning-of-line",
+        "end-of-line",
+        "backward-char",
+        "forward-char",
+        "backward-word",
+        "forward-word",
+        "backward-delete-char",
+        "delete-char",
+        "unix-word-rubout",
+        "backward-kill-word",
+        "kill-word",
+        "unix-line-discard",
+        "kill-line",
+        "yank",
+        "yank-pop",
+        "undo",
+        "redo",
+        "paste",
+        "normal-mode",
+        "insert-mode",
+        "quit",
+        "custom-1 to custom-19",
+        "execute",
+    ];
+
+    /// Parses an action name and its arguments. `None` is an explicitly unbound key.
+    fn parse(name: &str, args: Vec<String>) -> Result<Option<Self>, String> {
+        let action = match name {
+            "none" => return Ok(None),
+            "up" => Self::Up,
+            "down" => Self::Down,
+            "page-up" => Self::PageUp,
+            "page-down" => Self::PageDown,
+            "first" => Self::First,
+            "last" => Self::Last,
+            "toggle" => Self::Toggle,
+            "select-all" => Self::SelectAll,
+            "deselect-all" => Self::DeselectAll,
+            "submit" => Self::Submit,
+            "accept-query" => Self::AcceptQuery,
+            "clear-query" => Self::ClearQuery,
+            "beginning-of-line" => Self::BeginningOfLine,
+            "end-of-line" => Self::EndOfLine,
+            "backward-char" => Self::BackwardChar,
+            "forward-char" => Self::ForwardChar,
+            "backward-word" => Self::BackwardWord,
+            "forward-word" => Self::ForwardWord,
+            "backward-delete-char" => Self::BackwardDeleteChar,
+            "delete-char" => Self::DeleteChar,
+            "unix-word-rubout" => Self::UnixWordRubout,
+            "backward-kill-word" => Self::BackwardKillWord,
+            "kill-word" => Self::KillWord,
+            "unix-line-discard" => Self::UnixLineDiscard,
+            "kill-line" => Self::KillLine,
+            "yank" => Self::Yank,
+            "yank-pop" => Self::YankPop,
+            "undo" => Self::Undo,
+            "redo" => Self::Redo,
+            "paste" => Self::Paste,
+            "normal-mode" => Self::NormalMode,
+            "insert-mode" => Self::InsertMode,
+            "quit" => Self::Quit,
+            "execute" if args.is_empty() => {
+                return Err(String::from(
+                    "Provide a command to execute: `ctrl+o \"execute\" \"xdg-open\" \"$ITEM\"`",
+                ))
+            }
+            "execute" => return Ok(Some(Self::Execute(args))),
+            custom if custom.starts_with("custom-") => match custom["custom-".len()..].parse() {
+                Ok(n) if (1..=MAX_CUSTOM).contains(&n) => Self::Custom(n),
+                _ => {
+                    return Err(format!(
+                        "Custom actions go from custom-1 to custom-{MAX_CUSTOM}"
+                    ))
+                }
+            },
+            _ => {
+                return Err(format!(
+                    "Actions can be one of: {}, or `none` to unbind the key",
+                    Self::NAMES.join(", ")
+                ))
+            }
+        };
+        if args.is_empty() {
+            Ok(Some(action))
+        } else {
+            Err(format!("The `{name}` action does not take any arguments"))
+        }
+    }
+}
+
+/// Which keybindings are active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Keys type into the query
+    #[default]
+    Insert,
+    /// Keys move around and select items, as in vim
+    Normal,
+}
+
+/// The node inside `Keybindings` that holds the bindings for normal mode
+const NORMAL_MODE_KEY: &str = "normal";
+
+/// A sequence of key chords, like `g g`
+type Keys = Vec<Chord>;
+
+/// The result of looking up the keys pressed so far
+pub enum Lookup<'a> {
+    Action(&'a Action),
+    /// The keys are the start of a longer sequence
+    Prefix,
+    None,
+}
+
+/// Maps sequences of key chords to actions in each mode
+#[derive(Debug, Clone)]
+pub struct Keybindings {
+    insert: HashMap<Keys, Action>,
+    normal: HashMap<Keys, Action>,
+}
+
+fn bindings_from(bindings: &[(&str, Action)]) -> HashMap<Keys, Action> {
+    bindings
+        .iter()
+        .map(|(keys, action)| (parse_keys(keys).unwrap(), action.clone()))
+        .collect()
+}
+
+impl Keybindings {
+    /// The default keybindings. With `vim`, Escape switches to normal mode instead of quitting.
+    pub fn new(vim: bool) -> Self {
+        let mut insert = bindings_from(&[
+            ("up", Action::Up),
+            ("ctrl+k", Action::Up),
+            ("down", Action::Down),
+            ("ctrl+j", Action::Down),
+            ("page_up", Action::PageUp),
+            ("page_down", Action::PageDown),
+            ("home", Action::First),
+            ("end", Action::Last),
+            ("tab", Action::Toggle),
+            ("enter", Action::Submit),
+            ("numpad_enter", Action::Submit),
+            ("shift+enter", Action::AcceptQuery),
+            ("escape", Action::Quit),
+            ("ctrl+d", Action::Quit),
+            ("ctrl+a", Action::BeginningOfLine),
+            ("ctrl+e", Action::EndOfLine),
+            ("left", Action::BackwardChar),
+            ("ctrl+b", Action::BackwardChar),
+            ("right", Action::ForwardChar),
+            ("ctrl+f", Action::ForwardChar),
+            ("alt+b", Action::BackwardWord),
+            ("alt+f", Action::ForwardWord),
+            ("ctrl+left", Action::BackwardWord),
+            ("ctrl+right", Action::ForwardWord),
+            ("backspace", Action::BackwardDeleteChar),
+            ("ctrl+h", Action::BackwardDeleteChar),
+            ("delete", Action::DeleteChar),
+            ("ctrl+w", Action::UnixWordRubout),
+            ("alt+backspace", Action::BackwardKillWord),
+            ("ctrl+backspace", Action::BackwardKillWord),
+            ("alt+d", Action::KillWord),
+            ("ctrl+u", Action::UnixLineDiscard),
+            ("ctrl+y", Action::Yank),
+            ("alt+y", Action::YankPop),
+            ("ctrl+z", Action::Undo),
+            ("ctrl+shift+z", Action::Redo),
+            ("ctrl+v", Action::Paste),
+            ("shift+insert", Action::Paste),
+            ("alt+1", Action::Custom(1)),
+            ("alt+2", Action::Custom(2)),
+            ("alt+3", Action::Custom(3)),
+            ("alt+4", Action::Custom(4)),
+            ("alt+5", Action::Custom(5)),
+            ("alt+6", Action::Custom(6)),
+            ("alt+7", Action::Custom(7)),
+            ("alt+8", Action::Custom(8)),
+            ("alt+9", Action::Custom(9)),
+            ("alt+0", Action::Custom(10)),
+        ]);
+        if vim {
+            insert.extend(bindings_from(&[("escape", Action::NormalMode)]));
+        }
+        let normal = bindings_from(&[
+            ("j", Action::Down),
+            ("down", Action::Down),
+            ("k", Action::Up),
+            ("up", Action::Up),
+            ("ctrl+d", Action::PageDown),
+            ("page_down", Action::PageDown),
+            ("ctrl+u", Action::PageUp),
+            ("page_up", Action::PageUp),
+            ("g g", Action::First),
+            ("home", Action::First),
+            ("shift+g", Action::Last),
+            ("end", Action::Last),
+            ("x", Action::Toggle),
+            ("space", Action::Toggle),
+            ("tab", Action::Toggle),
+            ("slash", Action::InsertMode),
+            ("i", Action::InsertMode),
+            ("enter", Action::Submit),
+            ("numpad_enter", Action::Submit),
+            ("shift+enter", Action::AcceptQuery),
+            ("q", Action::Quit),
+            ("escape", Action::Quit),
+        ]);
+        Self { insert, normal }
+    }
+
+    pub fn lookup(&self, mode: Mode, keys: &[Chord]) -> Lookup<'_> {
+        let bindings = match mode {
+            Mode::Insert => &self.insert,
+            Mode::Normal => &self.normal,
+        };
+        if let Some(action) = bindings.get(keys) {
+            return Lookup::Action(action);
+        }
+        if bindings.keys().any(|k| k.starts_with(keys)) {
+            return Lookup::Prefix;
+        }
+        // Modifiers that aren't part of a binding are ignored, so Shift-Up still moves up.
+        // The binding with the most of the modifiers wins.
+        let matches = |bound: &[Chord]| bound.iter().zip(keys).all(|(b, k)| b.triggered_by(k));
+        let action = bindings
+            .iter()
+            .filter(|(bound, _)| bound.len() == keys.len() && matches(bound))
+            .max_by_key(|(bound, _)| specificity(bound));
+        match action {
+            Some((_, action)) => Lookup::Action(action),
+            None if bindings
+                .keys()
+                .any(|bound| bound.len() > keys.len() && matches(bound)) =>
+            {
+                Lookup::Prefix
+            }
+            None => Lookup::None,
+        }
+    }
+}
+
+/// How many modifiers a sequence of chords has, and which ones, so that the more specific
+/// of two bindings that both match some keys is always the same one
+fn specificity(keys: &[Chord]) -> (u32, Vec<u32>) {
+    let modifiers: Vec<u32> = keys.iter().map(|chord| chord.modifiers.bits()).collect();
+    (modifiers.iter().map(|m| m.count_ones()).sum(), modifiers)
+}
+
+/// Formats a sequence of key chords the way they are written in the config
+pub fn format_keys(keys: &[Chord]) -> String {
+    keys.iter()
+        .map(Chord::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a space-separated sequence of key chords
+fn parse_keys(s: &str) -> Option<Keys> {
+    let keys = s
+        .split_whitespace()
+        .map(Chord::parse)
+        .collect::<Option<Keys>>()?;
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// Parses a `Keybindings` node like `ctrl+o "execute" "xdg-open" "$ITEM"` on top of the
+/// defaults. Bindings for normal mode go in a `normal` node inside it.
+pub fn parse_keybindings(node: &KdlNode, vim: bool) -> Result<Keybindings, ConfigError> {
+    let mut result = Keybindings::new(vim);
+    let binding_definitions = node.children().map(|d| d.nodes()).unwrap_or_default();
+
+    for child in binding_definitions.iter() {
+        if child.name().value() == NORMAL_MODE_KEY {
+            let normal_definitions = child.children().map(|d| d.nodes()).unwrap_or_default();
+            for normal_child in normal_definitions.iter() {
+                parse_binding(normal_child, &mut result.normal)?;
+            }
+        } else {
+            parse_binding(child, &mut result.insert)?;
+        }
+    }
+    Ok(result)
+}
+
+fn parse_binding(node: &KdlNode, bindings: &mut HashMap<Keys, Action>) -> Result<(), ConfigError> {
+    let keys_name = node.name().value();
+    let keys = parse_keys(keys_name).ok_or_else(|| ConfigError::InvalidKeybinding {
+        chord_src: *node.name().span(),
+        help: format!(
+            "Write key chords like `ctrl+shift+k`, and sequences of them like `\"g g\"`. Modifiers can be one of: {}. Keys can be one of: {}",
+            MODIFIER_NAMES.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "),
+            KEY_NAMES.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", "),
+        ),
+    })?;
+    let mut args = node.entries().iter().filter(|e| e.name().is_none());
+    let action_def = args.next().ok_or_else(|| ConfigError::MissingArgument {
+        node_src: *node.span(),
+        help: format!("Provide an action for this key: `{keys_name} \"action\"`"),
+    })?;
+    let action_name = string_arg(action_def)?;
+    let action_args = args
+        .map(|e| string_arg(e).map(String::from))
+        .collect::<Result<Vec<_>, _>>()?;
+    let action =
+        Action::parse(action_name, action_args).map_err(|help| ConfigError::InvalidArgument {
+            arg_src: *action_def.span(),
+            help,
+        })?;
+    match action {
+        Some(action) => bindings.insert(keys, action),
+        None => bindings.remove(&keys),
+    };
+    Ok(())
+}
+
+fn string_arg(entry: &KdlEntry) -> Result<&str, ConfigError> {
+    entry
+        .value()
+        .as_string()
+        .ok_or_else(|| ConfigError::InvalidArgument {
+            arg_src: *entry.span(),
+            help: String::from("Actions and their arguments should be strings"),
+        })
+}
+
+/// Runs the command of an `execute` action, waiting for it to finish
+pub async fn execute(command: Vec<String>, item: String, query: String) -> Result<(), MenuError> {
+    let (program, args) = command
+        .split_first()
+        .unwrap_or_else(|| unreachable!("execute actions are parsed with a command"));
+    let output = Command::new(program)
+        .args(args.iter().map(|a| match a.as_str() {
+            ITEM_VAR_NAME => &item,
+            QUERY_VAR_NAME => &query,
+            _ => a,
+        }))
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| MenuError::CallbackSpawn {
+            program: program.clone(),
+            message: e.to_string(),
+        })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(MenuError::CallbackFailed {
+            program: program.clone(),
+            status: output.status.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(bindings: &Keybindings, mode: Mode, keys: &str) -> Option<Action> {
+        match bindings.lookup(mode, &parse_keys(keys).unwrap()) {
+            Lookup::Action(action) => Some(action.clone()),
+            Lookup::Prefix | Lookup::None => None,
+        }
+    }
+
+    #[test]
+    fn extra_modifiers_are_ignored() {
+        let bindings = Keybindings::new(false);
+        assert_eq!(lookup(&bindings, Mode::Insert, "up"), Some(Action::Up));
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "shift+up"),
+            Some(Action::Up)
+        );
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "ctrl+shift+backspace"),
+            Some(Action::BackwardKillWord)
+        );
+        // Bindings with the modifiers come first
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "shift+enter"),
+            Some(Action::AcceptQuery)
+        );
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "ctrl+shift+enter"),
+            Some(Action::AcceptQuery)
+        );
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "ctrl+shift+z"),
+            Some(Action::Redo)
+        );
+        assert_eq!(
+            lookup(&bindings, Mode::Insert, "ctrl+alt+z"),
+            Some(Action::Undo)
+        );
+        // Missing modifiers aren't
+        assert_eq!(lookup(&bindings, Mode::Insert, "k"), None);
+    }
+
+    #[test]
+    fn sequences() {
+        let bindings = Keybindings::new(false);
+        let prefix = |keys| {
+            matches!(
+                bindings.lookup(Mode::Normal, &parse_keys(keys).unwrap()),
+                Lookup::Prefix
+            )
+        };
+        assert!(prefix("g"));
+        assert!(prefix("ctrl+g"));
+        assert_eq!(
+            lookup(&bindings, Mode::Normal, "g ctrl+g"),
+            Some(Action::First)
+        );
+        assert_eq!(
+            lookup(&bindings, Mode::Normal, "shift+g"),
+            Some(Action::Last)
+        );
+    }
+}
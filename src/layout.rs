@@ -1,5 +1,5 @@
+use crate::{Reflective, UpdateFromOther};
 use iced::Element;
-use icedmenu::{Reflective, UpdateFromOther};
 use kdl::KdlNode;
 
 use crate::app::{IcedMenu, Message};
@@ -11,10 +11,16 @@ use self::style::{GenericStyle, State, StyleLookup};
 
 pub mod column;
 pub mod container;
+pub mod icon;
+pub mod item_field;
 pub mod item_key;
 pub mod items;
+pub mod loading;
+pub mod preview;
 pub mod query;
 pub mod row;
+pub mod scrollbar;
+pub mod status;
 pub mod style;
 pub mod text;
 
@@ -30,9 +36,15 @@ pub enum LayoutNode {
     Row(NodeData),
     Column(NodeData),
     Query(Box<query::QueryNodeData>),
-    Items(ItemsNodeData),
+    Items(Box<ItemsNodeData>),
     ItemKey(Box<item_key::ItemKeyNodeData>),
+    ItemField(Box<item_field::ItemFieldNodeData>),
     Text(Box<text::TextNodeData>),
+    Loading(Box<text::TextNodeData>),
+    Status(Box<status::StatusNodeData>),
+    Scrollbar(Box<scrollbar::ScrollbarNodeData>),
+    Preview(Box<preview::PreviewNodeData>),
+    Icon(Box<icon::IconNodeData>),
 }
 
 impl LayoutNode {
@@ -70,6 +82,19 @@ impl LayoutNode {
             "Row" => row::new(children, style),
             "Column" | "Col" => column::new(children, style),
             "Text" => text::new(node, children, style),
+            "Loading" => loading::new(node, children, style),
+            "Status" => status::new(node, children, style),
+            "Preview" => preview::new(node, children, style),
+            "Icon" => icon::new(node, children, style),
+            "Scrollbar" => {
+                let mut thumb_style = style;
+                thumb_style.update_from(&style_lookup.style_for(
+                    &style_names,
+                    node_type,
+                    State::Thumb,
+                ));
+                scrollbar::new(node, children, style, thumb_style)
+            }
             "Query" => {
                 let mut focused_style = style;
                 focused_style.update_from(&style_lookup.style_for(
@@ -83,7 +108,27 @@ impl LayoutNode {
                     node_type,
                     State::Hovered,
                 ));
-                query::new(node, children, style, focused_style, hovered_style)
+                let mut normal_style = style;
+                normal_style.update_from(&style_lookup.style_for(
+                    &style_names,
+                    node_type,
+                    State::Normal,
+                ));
+                let mut invalid_style = style;
+                invalid_style.update_from(&style_lookup.style_for(
+                    &style_names,
+                    node_type,
+                    State::Invalid,
+                ));
+                query::new(
+                    node,
+                    children,
+                    style,
+                    focused_style,
+                    hovered_style,
+                    normal_style,
+                    invalid_style,
+                )
             }
             "Items" => {
                 let mut hovered_style = style;
@@ -113,7 +158,7 @@ impl LayoutNode {
                     selected_style,
                 )
             }
-            "ItemKey" => {
+            "ItemKey" | "ItemField" => {
                 let mut hovered_style = style;
                 hovered_style.update_from(&style_lookup.style_for(
                     &style_names,
@@ -126,7 +171,11 @@ impl LayoutNode {
                     node_type,
                     State::Selected,
                 ));
-                item_key::new(node, children, style, hovered_style, selected_style)
+                if node_type == "ItemKey" {
+                    item_key::new(node, children, style, hovered_style, selected_style)
+                } else {
+                    item_field::new(node, children, style, hovered_style, selected_style)
+                }
             }
             _ => Err(ConfigError::InvalidLayoutNode {
                 node_src: *node.name().span(),
@@ -149,7 +198,13 @@ impl LayoutNode {
             Self::Column(data) => column::view(data, menu, item),
             Self::Query(data) => query::view(data, menu),
             Self::Text(data) => text::view(data),
+            Self::Loading(data) => loading::view(data, menu),
+            Self::Status(data) => status::view(data, menu),
+            Self::Scrollbar(data) => scrollbar::view(data, menu),
+            Self::Preview(data) => preview::view(data, menu),
             Self::ItemKey(data) => item_key::view(data, menu, item),
+            Self::ItemField(data) => item_field::view(data, menu, item),
+            Self::Icon(data) => icon::view(data, item),
             Self::Items(_) => {
                 // Layouts are validated so that Items must be the child of a Row or Column
                 // (which call item::view() directly) so this branch should never be reached
@@ -165,7 +220,13 @@ impl LayoutNode {
             Self::Column(data) => column::height(data, menu, item),
             Self::Query(data) => query::height(data),
             Self::Text(data) => text::height(data),
+            Self::Loading(data) => loading::height(data, menu),
+            Self::Status(data) => status::height(data, menu),
+            Self::Scrollbar(data) => scrollbar::height(data, menu),
+            Self::Preview(data) => preview::height(data),
             Self::ItemKey(data) => item_key::height(data, menu, item),
+            Self::ItemField(data) => item_field::height(data, menu, item),
+            Self::Icon(data) => icon::height(data),
             Self::Items(_) => {
                 // Layouts are validated so that Items must be the child of a Row or Column
                 // (which call item::height() directly) so this branch should never be reached
@@ -180,7 +241,13 @@ impl LayoutNode {
             Self::Column(data) => column::width(data, menu, item),
             Self::Query(data) => query::width(data, menu),
             Self::Text(data) => text::width(data),
+            Self::Loading(data) => loading::width(data, menu),
+            Self::Status(data) => status::width(data, menu),
+            Self::Scrollbar(data) => scrollbar::width(data, menu),
+            Self::Preview(data) => preview::width(data),
             Self::ItemKey(data) => item_key::width(data, menu, item),
+            Self::ItemField(data) => item_field::width(data, menu, item),
+            Self::Icon(data) => icon::width(data),
             Self::Items(_) => {
                 // Layouts are validated so that Items must be the child of a Row or Column
                 // (which call item::width() directly) so this branch should never be reached
@@ -188,6 +255,26 @@ impl LayoutNode {
             }
         }
     }
+
+    /// The first Items node
+    pub fn items(node: &Self) -> Option<&ItemsNodeData> {
+        match node {
+            Self::Items(data) => Some(data),
+            Self::Container(data) => Self::items(&data.child),
+            Self::Row(data) | Self::Column(data) => data.children.iter().find_map(Self::items),
+            _ => None,
+        }
+    }
+
+    /// The first Preview node, outside of Items
+    pub fn preview(node: &Self) -> Option<&preview::PreviewNodeData> {
+        match node {
+            Self::Preview(data) => Some(data),
+            Self::Container(data) => Self::preview(&data.child),
+            Self::Row(data) | Self::Column(data) => data.children.iter().find_map(Self::preview),
+            _ => None,
+        }
+    }
 }
 
 fn validate_children(
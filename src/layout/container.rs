@@ -1,6 +1,6 @@
+use crate::{apply_height_styles, apply_styles, apply_width_styles};
 use iced::widget::container::{Appearance, StyleSheet};
 use iced::{widget, Element};
-use icedmenu::{apply_height_styles, apply_styles, apply_width_styles};
 use kdl::KdlNode;
 
 use super::LayoutNode;
@@ -15,13 +15,13 @@ pub struct ContainerNodeData {
     pub style: GenericStyle,
 }
 
-struct ContainerTheme {
+pub(super) struct ContainerTheme {
     style: GenericStyle,
     default_theme: iced::theme::Container,
 }
 
 impl ContainerTheme {
-    fn create(style: GenericStyle) -> iced::theme::Container {
+    pub(super) fn create(style: GenericStyle) -> iced::theme::Container {
         iced::theme::Container::Custom(Box::from(Self {
             style,
             default_theme: iced::theme::Container::default(),
@@ -0,0 +1,103 @@
+use crate::{apply_height_styles, apply_width_styles};
+use iced::widget::{image, svg, Space};
+use iced::{Element, Length};
+use kdl::KdlNode;
+use std::path::PathBuf;
+
+use super::style::{string_property, GenericStyle};
+use super::LayoutNode;
+use crate::app::Message;
+use crate::config::ConfigError;
+use crate::item::Item;
+
+const DEFAULT_SIZE: u32 = 32;
+const DEFAULT_FIELD: &str = "icon";
+const DEFAULT_THEME: &str = "hicolor";
+
+/// Shows the image in a field of the item, which is either a path or the name of an icon in
+/// the icon `theme`. When the item doesn't have one, the `fallback` icon is shown, or else
+/// empty space. The size is the `width` and `height` styles, 32 by default.
+#[derive(Debug)]
+pub struct IconNodeData {
+    pub field: String,
+    pub fallback: Option<String>,
+    pub theme: String,
+    pub scale: u32,
+    pub style: GenericStyle,
+}
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    let scale = match node.get("scale") {
+        Some(v) => v
+            .value()
+            .as_i64()
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidArgument {
+                arg_src: *v.span(),
+                help: "scale should be a positive number: `Icon scale=2`".to_string(),
+            })?,
+        None => 1,
+    };
+    Ok(LayoutNode::Icon(Box::new(IconNodeData {
+        field: string_property(node, "field")?.unwrap_or_else(|| DEFAULT_FIELD.to_string()),
+        fallback: string_property(node, "fallback")?,
+        theme: string_property(node, "theme")?.unwrap_or_else(|| DEFAULT_THEME.to_string()),
+        scale,
+        style,
+    })))
+}
+
+fn size(data: &IconNodeData) -> (u32, u32) {
+    let style = &data.style;
+    (
+        apply_width_styles!(DEFAULT_SIZE, style),
+        apply_height_styles!(DEFAULT_SIZE, style),
+    )
+}
+
+fn icon_path(data: &IconNodeData, item: &Item) -> Option<PathBuf> {
+    let (width, height) = size(data);
+    let find = |name: &str| crate::icon::find(name, width.max(height), data.scale, &data.theme);
+    item.data
+        .field(&data.field)
+        .and_then(|name| find(&name))
+        .or_else(|| find(data.fallback.as_deref()?))
+}
+
+pub fn view<'a>(data: &IconNodeData, item: Option<&'a Item>) -> Element<'a, Message> {
+    let item = item.expect("no Item provided to Icon");
+    let (width, height) = size(data);
+    let (width, height) = (Length::Fixed(width as f32), Length::Fixed(height as f32));
+    match icon_path(data, item) {
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("svg")) =>
+        {
+            svg(svg::Handle::from_path(path))
+                .width(width)
+                .height(height)
+                .into()
+        }
+        Some(path) => image(image::Handle::from_path(path))
+            .width(width)
+            .height(height)
+            .into(),
+        None => Space::new(width, height).into(),
+    }
+}
+
+/// Icons take up their size even when the item has none, so that items line up
+pub fn height(data: &IconNodeData) -> u32 {
+    size(data).1
+}
+
+pub fn width(data: &IconNodeData) -> u32 {
+    size(data).0
+}
@@ -0,0 +1,92 @@
+use crate::{apply_height_styles, get_item_style, UpdateFromOther};
+use iced::widget::Space;
+use iced::{Element, Length};
+use kdl::KdlNode;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::item_key::{self, UNSTYLED};
+use super::style::{string_property, GenericStyle};
+use super::LayoutNode;
+use crate::app::{IcedMenu, Message};
+use crate::config::ConfigError;
+use crate::item::Item;
+
+/// Shows a field of the item, like `description` or any extra field of JSON items.
+/// When the item doesn't have the field, `fallback` is shown, or else nothing.
+#[derive(Debug)]
+pub struct ItemFieldNodeData {
+    pub name: String,
+    pub fallback: Option<String>,
+    pub style: GenericStyle,
+    pub hovered_style: GenericStyle,
+    pub selected_style: GenericStyle,
+}
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+    hovered_style: GenericStyle,
+    selected_style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    let name = string_property(node, "name")?.ok_or_else(|| ConfigError::MissingArgument {
+        node_src: *node.span(),
+        help: "ItemField nodes require the name of a field: `ItemField name=\"description\"`"
+            .to_string(),
+    })?;
+    Ok(LayoutNode::ItemField(Box::new(ItemFieldNodeData {
+        name,
+        fallback: string_property(node, "fallback")?,
+        style,
+        hovered_style,
+        selected_style,
+    })))
+}
+
+/// The text of the field, and whether it is the item's own rather than the fallback
+fn field_text(data: &ItemFieldNodeData, item: &Item) -> Option<(String, bool)> {
+    match item.data.field(&data.name) {
+        Some(text) => Some((text.into_owned(), true)),
+        None => data.fallback.clone().map(|fallback| (fallback, false)),
+    }
+}
+
+pub fn view<'a>(
+    data: &ItemFieldNodeData,
+    menu: &IcedMenu,
+    item: Option<&'a Item>,
+) -> Element<'a, Message> {
+    let item = item.expect("no Item provided to ItemField");
+    let Some((text, is_field)) = field_text(data, item) else {
+        return Space::new(Length::Shrink, Length::Shrink).into();
+    };
+    let style = get_item_style!(item, data, menu);
+    // The fallback never matched the query
+    let match_indices = is_field
+        .then(|| item.match_indices.get(&data.name))
+        .flatten();
+    item_key::text_view(vec![(&text, &UNSTYLED)], match_indices, style)
+}
+
+pub fn height(data: &ItemFieldNodeData, menu: &IcedMenu, item: Option<&Item>) -> u32 {
+    let item = item.expect("no Item provided to ItemField");
+    if field_text(data, item).is_none() {
+        return 0;
+    }
+    let style = get_item_style!(item, data, menu);
+    apply_height_styles!(
+        style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32,
+        style
+    )
+}
+
+pub fn width(data: &ItemFieldNodeData, menu: &IcedMenu, item: Option<&Item>) -> u32 {
+    let item = item.expect("no Item provided to ItemField");
+    let Some((text, _)) = field_text(data, item) else {
+        return 0;
+    };
+    let style = get_item_style!(item, data, menu);
+    (text.graphemes(true).count() as f32 * 0.7) as u32
+        * style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32
+}
@@ -1,14 +1,24 @@
+use crate::{apply_height_styles, apply_styles, get_item_style, UpdateFromOther};
 use iced::widget::{text, Row};
 use iced::Element;
-use icedmenu::{apply_height_styles, apply_styles, get_item_style, UpdateFromOther};
 use kdl::KdlNode;
+use unicode_segmentation::UnicodeSegmentation;
 
 use super::style::GenericStyle;
 use super::LayoutNode;
+use crate::ansi::Span;
 use crate::app::{IcedMenu, Message};
 use crate::config::ConfigError;
 use crate::item::Item;
 
+pub(super) static UNSTYLED: Span = Span {
+    text: String::new(),
+    color: None,
+    background: None,
+    bold: false,
+    italic: false,
+};
+
 #[derive(Debug)]
 pub struct ItemKeyNodeData {
     pub style: GenericStyle,
@@ -39,15 +49,39 @@ pub fn view<'a>(
     let item = item.expect("no Item provided to ItemKey");
     // Use hovered style if this item is under the cursor
     let style = get_item_style!(item, data, menu);
-    let mut content = Vec::new();
+    let match_indices = item.match_indices.get("key");
+    if let Some(view) = &item.data.display_key {
+        // Matches are positions in the whole key
+        let match_indices = match_indices.map(|indices| view.view_indices(indices));
+        return text_view(vec![(&view.text, &UNSTYLED)], match_indices.as_ref(), style);
+    }
+    // Keys styled with `--ansi` or `--markup` are split into spans with their own styles
+    let segments: Vec<(&str, &Span)> = match &item.data.styled_key {
+        Some(spans) => spans
+            .iter()
+            .map(|span| (span.text.as_str(), span))
+            .collect(),
+        None => vec![(item.data.key.as_str(), &UNSTYLED)],
+    };
+    text_view(segments, match_indices, style)
+}
 
-    // Item text with match highlights
-    let mut texts: Vec<Element<Message>> = item
-        .data
-        .key
-        .char_indices()
-        .map(|(i, c)| {
-            let mut t = text(c);
+/// Item text with match highlights. A grapheme is highlighted if any of its chars matched.
+/// Tabs are shown as spaces up to the next tab stop, and other control characters not at all.
+pub(super) fn text_view<'a>(
+    segments: Vec<(&str, &Span)>,
+    match_indices: Option<&Vec<usize>>,
+    style: GenericStyle,
+) -> Element<'a, Message> {
+    let mut char_index = 0;
+    let mut column = 0;
+    let texts: Vec<Element<Message>> = segments
+        .into_iter()
+        .flat_map(|(segment, span)| segment.graphemes(true).map(move |g| (g, span)))
+        .map(|(grapheme, span)| {
+            let chars = char_index..char_index + grapheme.chars().count();
+            char_index = chars.end;
+            let mut t = text(crate::ansi::expand_tabs(grapheme, &mut column));
             t = apply_styles!(
                 t,
                 style;
@@ -57,17 +91,16 @@ pub fn view<'a>(
                 size: font_size,
             );
             // Sets the color of the text that matches the query string
-            match (&item.match_indices, style.match_text_color) {
-                (Some(indices), Some(color)) if indices.contains(&i) => {
-                    t = t.style(color);
+            let match_color = match (match_indices, style.match_text_color) {
+                (Some(indices), Some(color)) if chars.clone().any(|i| indices.contains(&i)) => {
+                    Some(color)
                 }
-                _ => (),
-            }
-            t.into()
+                _ => None,
+            };
+            super::text::span_view(t, span, style.font, match_color)
         })
         .collect();
-    content.append(&mut texts);
-    Row::with_children(content)
+    Row::with_children(texts)
         .spacing(0)
         .padding(0)
         .width(iced::Length::Shrink)
@@ -86,6 +119,10 @@ pub fn height(data: &ItemKeyNodeData, menu: &IcedMenu, item: Option<&Item>) -> u
 pub fn width(data: &ItemKeyNodeData, menu: &IcedMenu, item: Option<&Item>) -> u32 {
     let item = item.expect("no Item provided to ItemKey");
     let style = get_item_style!(item, data, menu);
-    (item.data.key.chars().count() as f32 * 0.7) as u32
+    let text = match &item.data.display_key {
+        Some(view) => &view.text,
+        None => &item.data.key,
+    };
+    (text.graphemes(true).count() as f32 * 0.7) as u32
         * style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32
 }
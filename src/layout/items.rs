@@ -1,8 +1,8 @@
-use iced::widget::button::{Appearance, StyleSheet};
-use iced::Element;
-use icedmenu::{
+use crate::{
     apply_height_styles, apply_styles, apply_width_styles, get_item_style, UpdateFromOther,
 };
+use iced::widget::button::{Appearance, StyleSheet};
+use iced::Element;
 use kdl::KdlNode;
 
 use super::style::GenericStyle;
@@ -37,13 +37,13 @@ pub fn new(
             ),
         }),
     }?;
-    Ok(LayoutNode::Items(ItemsNodeData {
+    Ok(LayoutNode::Items(Box::new(ItemsNodeData {
         child: Box::new(children.into_iter().next().unwrap()),
         style,
         hovered_style,
         pressed_style,
         selected_style,
-    }))
+    })))
 }
 
 struct ButtonTheme {
@@ -106,7 +106,7 @@ impl StyleSheet for ButtonTheme {
 }
 
 pub fn views<'a>(data: &'a ItemsNodeData, menu: &'a IcedMenu) -> Vec<Element<'a, Message>> {
-    menu.visible_items
+    menu.visible_items()
         .iter()
         .map(|item_index| {
             let item = &menu.items[*item_index];
@@ -125,7 +125,7 @@ pub fn views<'a>(data: &'a ItemsNodeData, menu: &'a IcedMenu) -> Vec<Element<'a,
 }
 
 pub fn heights(data: &ItemsNodeData, menu: &IcedMenu) -> Vec<u32> {
-    menu.visible_items
+    menu.visible_items()
         .iter()
         .map(|item_index| {
             let item = &menu.items[*item_index];
@@ -138,7 +138,7 @@ pub fn heights(data: &ItemsNodeData, menu: &IcedMenu) -> Vec<u32> {
 }
 
 pub fn widths(data: &ItemsNodeData, menu: &IcedMenu) -> Vec<u32> {
-    menu.visible_items
+    menu.visible_items()
         .iter()
         .map(|item_index| {
             let item = &menu.items[*item_index];
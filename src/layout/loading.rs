@@ -0,0 +1,55 @@
+use iced::{widget, Element};
+use kdl::KdlNode;
+
+use super::style::GenericStyle;
+use super::text::{self, TextNodeData};
+use super::LayoutNode;
+use crate::app::{IcedMenu, Message};
+use crate::config::ConfigError;
+
+const DEFAULT_VALUE: &str = "Loading…";
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    let value = match node.get("value") {
+        Some(v) => v
+            .value()
+            .as_string()
+            .ok_or_else(|| ConfigError::InvalidArgument {
+                arg_src: *v.span(),
+                help: "The value for a Loading node should be a string: `Loading value=\"value\"`"
+                    .to_string(),
+            })?
+            .to_string(),
+        None => DEFAULT_VALUE.to_string(),
+    };
+    Ok(LayoutNode::Loading(Box::new(TextNodeData { style, value })))
+}
+
+pub fn view<'a>(data: &'a TextNodeData, menu: &IcedMenu) -> Element<'a, Message> {
+    if menu.loading {
+        text::view(data)
+    } else {
+        widget::Space::new(iced::Length::Shrink, iced::Length::Shrink).into()
+    }
+}
+
+pub fn height(data: &TextNodeData, menu: &IcedMenu) -> u32 {
+    if menu.loading {
+        text::height(data)
+    } else {
+        0
+    }
+}
+
+pub fn width(data: &TextNodeData, menu: &IcedMenu) -> u32 {
+    if menu.loading {
+        text::width(data)
+    } else {
+        0
+    }
+}
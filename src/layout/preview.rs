@@ -0,0 +1,133 @@
+use crate::{apply_height_styles, apply_styles, apply_width_styles};
+use iced::widget::{self, scrollable};
+use iced::{Element, Length};
+use kdl::KdlNode;
+
+use super::container::ContainerTheme;
+use super::style::GenericStyle;
+use super::{text, LayoutNode};
+use crate::ansi::Line;
+use crate::app::{IcedMenu, Message};
+use crate::config::ConfigError;
+
+pub const PREVIEW_SCROLLABLE_ID: &str = "preview";
+
+const DEFAULT_WIDTH: u32 = 400;
+const DEFAULT_LINES: u32 = 10;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+
+/// Shows what `command` prints for the item under the cursor, in a scrollable area.
+/// `{key}`, `{value}` and other `{field}`s in the command are replaced by that field.
+/// At most `max-bytes` of output are read. Colors from ANSI escape sequences are shown, and
+/// output without any is highlighted as code by the item's file extension if the node has a
+/// `syntax_theme` style.
+#[derive(Debug)]
+pub struct PreviewNodeData {
+    pub style: GenericStyle,
+    pub command: String,
+    pub max_bytes: usize,
+}
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    let command = match node.get("command") {
+        Some(v) => v
+            .value()
+            .as_string()
+            .ok_or_else(|| ConfigError::InvalidArgument {
+                arg_src: *v.span(),
+                help: "The command for a Preview node should be a string: `Preview command=\"cat {value}\"`"
+                    .to_string(),
+            })?
+            .to_string(),
+        None => {
+            return Err(ConfigError::MissingArgument {
+                node_src: *node.span(),
+                help: "Preview nodes require a command: `Preview command=\"cat {value}\"`"
+                    .to_string(),
+            })
+        }
+    };
+    let max_bytes = match node.get("max-bytes") {
+        Some(v) => v
+            .value()
+            .as_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidArgument {
+                arg_src: *v.span(),
+                help: "max-bytes should be a positive number: `Preview max-bytes=65536`"
+                    .to_string(),
+            })?,
+        None => DEFAULT_MAX_BYTES,
+    };
+    Ok(LayoutNode::Preview(Box::new(PreviewNodeData {
+        style,
+        command,
+        max_bytes,
+    })))
+}
+
+fn text_view<'a>(text: &str, style: &GenericStyle) -> widget::Text<'a> {
+    let text = widget::text(text);
+    apply_styles!(
+        text,
+        style;
+        font;
+        style: text_color,
+        size: font_size,
+    )
+}
+
+fn line_view<'a>(line: &'a Line, style: &GenericStyle) -> Element<'a, Message> {
+    if line.is_empty() {
+        // An empty row would have no height
+        return text_view(" ", style).into();
+    }
+    let spans = line
+        .iter()
+        .map(|span| text::span_view(text_view(&span.text, style), span, style.font, None));
+    widget::row(spans.collect()).into()
+}
+
+pub fn view<'a>(data: &'a PreviewNodeData, menu: &'a IcedMenu) -> Element<'a, Message> {
+    let style = &data.style;
+    let text = match menu.preview_output() {
+        Some(Err(e)) => widget::column(vec![text_view(&e.to_string(), style).into()]),
+        output => {
+            let lines = output.and_then(Result::ok).unwrap_or_default();
+            widget::column(lines.iter().map(|line| line_view(line, style)).collect())
+        }
+    };
+    let area = scrollable(text)
+        .id(scrollable::Id::new(PREVIEW_SCROLLABLE_ID))
+        .width(Length::Fill)
+        .height(Length::Fill);
+    let result = widget::container(area)
+        .width(Length::Fixed(width(data) as f32))
+        .height(Length::Fixed(height(data) as f32))
+        .style(ContainerTheme::create(*style));
+    apply_styles!(
+        result,
+        style;
+        padding;
+    )
+    .into()
+}
+
+/// The preview has a fixed size, so that the window doesn't change size with every item
+pub fn height(data: &PreviewNodeData) -> u32 {
+    let style = &data.style;
+    let font = style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32;
+    let padding = style.padding.unwrap_or(0) as u32;
+    apply_height_styles!(DEFAULT_LINES * font + 2 * padding, style)
+}
+
+pub fn width(data: &PreviewNodeData) -> u32 {
+    let style = &data.style;
+    apply_width_styles!(DEFAULT_WIDTH, style)
+}
@@ -1,18 +1,25 @@
+use crate::{apply_styles, apply_width_styles};
 use iced::widget::text_input::{Appearance, StyleSheet};
-use iced::{widget, Color, Element};
-use icedmenu::{apply_styles, apply_width_styles};
+use iced::{event, widget, Color, Element, Event, Length, Point, Rectangle};
+use iced_native::widget::{tree, Operation, Tree, Widget};
+use iced_native::{layout, renderer, Clipboard, Layout, Shell};
 use kdl::KdlNode;
 
 use super::style::GenericStyle;
 use super::LayoutNode;
 use crate::app::{IcedMenu, Message};
 use crate::config::ConfigError;
+use crate::keybindings::Mode;
 
 #[derive(Debug)]
 pub struct QueryNodeData {
     pub style: GenericStyle,
     pub focused_style: GenericStyle,
     pub hovered_style: GenericStyle,
+    /// Used instead of the other styles in vim-style normal mode
+    pub normal_style: GenericStyle,
+    /// Used instead of the other styles when the submitted query failed validation
+    pub invalid_style: GenericStyle,
 }
 
 pub fn new(
@@ -21,12 +28,16 @@ pub fn new(
     style: GenericStyle,
     focused_style: GenericStyle,
     hovered_style: GenericStyle,
+    normal_style: GenericStyle,
+    invalid_style: GenericStyle,
 ) -> Result<LayoutNode, ConfigError> {
     super::validate_children(node, children.len(), 0)?;
     Ok(LayoutNode::Query(Box::new(QueryNodeData {
         style,
         focused_style,
         hovered_style,
+        normal_style,
+        invalid_style,
     })))
 }
 
@@ -110,18 +121,137 @@ impl StyleSheet for TextInputTheme {
     }
 
     fn disabled_color(&self, style: &Self::Style) -> Color {
-        style.disabled_color(&self.default_theme)
+        // The query is only disabled in normal mode, where it should still be readable
+        self.style
+            .text_color
+            .unwrap_or_else(|| style.disabled_color(&self.default_theme))
+    }
+}
+
+/// Shows a text input without letting it handle keys or clicks, so that the query and its
+/// cursor are only changed by the menu's `QueryEditor`
+struct QueryDisplay<'a, Renderer> {
+    input: iced_native::Element<'a, Message, Renderer>,
+}
+
+impl<'a, Renderer> Widget<Message, Renderer> for QueryDisplay<'a, Renderer>
+where
+    Renderer: iced_native::Renderer,
+{
+    fn width(&self) -> Length {
+        self.input.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.input.as_widget().height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.input.as_widget().layout(renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        state: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.input.as_widget().draw(
+            state,
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor_position,
+            viewport,
+        )
+    }
+
+    fn tag(&self) -> tree::Tag {
+        self.input.as_widget().tag()
+    }
+
+    fn state(&self) -> tree::State {
+        self.input.as_widget().state()
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.input.as_widget().children()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        self.input.as_widget().diff(tree)
+    }
+
+    fn operate(
+        &self,
+        state: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.input
+            .as_widget()
+            .operate(state, layout, renderer, operation)
+    }
+
+    fn on_event(
+        &mut self,
+        state: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        match event {
+            // Window events still make the cursor blink
+            Event::Keyboard(_) | Event::Mouse(_) | Event::Touch(_) => event::Status::Ignored,
+            _ => self.input.as_widget_mut().on_event(
+                state,
+                event,
+                layout,
+                cursor_position,
+                renderer,
+                clipboard,
+                shell,
+            ),
+        }
+    }
+}
+
+impl<'a, Renderer> From<QueryDisplay<'a, Renderer>> for iced_native::Element<'a, Message, Renderer>
+where
+    Renderer: iced_native::Renderer + 'a,
+{
+    fn from(display: QueryDisplay<'a, Renderer>) -> Self {
+        Self::new(display)
     }
 }
 
 pub const QUERY_INPUT_ID: &str = "query_input";
 pub fn view<'a>(data: &QueryNodeData, menu: &IcedMenu) -> Element<'a, Message> {
-    let result = widget::text_input(&menu.cli_args.prompt, &menu.query)
-        .on_input(Message::QueryChanged)
-        .on_submit(Message::Submitted)
+    let mut result = widget::text_input(&menu.prompt, menu.query.text())
         .id(widget::text_input::Id::new(QUERY_INPUT_ID));
+    // The input only shows its cursor while it is editable, which it isn't in normal mode.
+    // It never gets to edit the query itself.
+    if menu.mode == Mode::Insert {
+        result = result.on_input(Message::QueryChanged);
+    }
+    let theme = if menu.query_invalid {
+        TextInputTheme::create(data.invalid_style, data.invalid_style, data.invalid_style)
+    } else if menu.mode == Mode::Normal {
+        TextInputTheme::create(data.normal_style, data.normal_style, data.normal_style)
+    } else {
+        TextInputTheme::create(data.style, data.focused_style, data.hovered_style)
+    };
     let style = &data.style;
-    apply_styles!(
+    let result = apply_styles!(
         result,
         style;
         font,
@@ -129,11 +259,10 @@ pub fn view<'a>(data: &QueryNodeData, menu: &IcedMenu) -> Element<'a, Message> {
         padding;
         size: font_size,
     )
-    .style(TextInputTheme::create(
-        *style,
-        data.focused_style,
-        data.hovered_style,
-    ))
+    .style(theme);
+    QueryDisplay {
+        input: result.into(),
+    }
     .into()
 }
 
@@ -149,8 +278,8 @@ pub fn width(data: &QueryNodeData, menu: &IcedMenu) -> u32 {
     let font = style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32;
     let padding = style.padding.unwrap_or(0) as u32;
     let text_width = (std::cmp::max(
-        menu.cli_args.prompt.chars().count(),
-        menu.query.chars().count(),
+        menu.prompt.chars().count(),
+        menu.query.text().chars().count(),
     ) as f32
         * 0.7) as u32
         * font;
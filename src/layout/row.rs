@@ -1,5 +1,5 @@
+use crate::{apply_height_styles, apply_styles, apply_width_styles};
 use iced::{widget, Element};
-use icedmenu::{apply_height_styles, apply_styles, apply_width_styles};
 
 use super::style::GenericStyle;
 use super::{items, LayoutNode, NodeData};
@@ -68,7 +68,7 @@ pub fn width(data: &NodeData, menu: &IcedMenu, item: Option<&Item>) -> u32 {
         .children
         .iter()
         .map(|c| match c {
-            LayoutNode::Items(_) => menu.visible_items.len() as u32,
+            LayoutNode::Items(_) => menu.visible_items().len() as u32,
             _ => 1,
         })
         .sum::<u32>();
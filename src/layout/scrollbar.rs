@@ -0,0 +1,88 @@
+use crate::{apply_height_styles, apply_width_styles};
+use iced::widget::{self, Space};
+use iced::{Element, Length};
+use kdl::KdlNode;
+
+use super::container::ContainerTheme;
+use super::style::GenericStyle;
+use super::LayoutNode;
+use crate::app::{IcedMenu, Message};
+use crate::config::ConfigError;
+
+const DEFAULT_WIDTH: f32 = 6.0;
+
+/// Shows the scroll position within all matching items. Place it in a Row next to the Column
+/// containing Items. The track is styled as `Scrollbar` and the thumb as `Scrollbar:thumb`.
+#[derive(Debug)]
+pub struct ScrollbarNodeData {
+    pub style: GenericStyle,
+    pub thumb_style: GenericStyle,
+}
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+    thumb_style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    Ok(LayoutNode::Scrollbar(Box::new(ScrollbarNodeData {
+        style,
+        thumb_style,
+    })))
+}
+
+/// The scrollbar is only shown when some matches don't fit in the window
+fn is_visible(menu: &IcedMenu) -> bool {
+    menu.matched_items.len() > menu.visible_items().len()
+}
+
+fn bar_width(style: &GenericStyle) -> Length {
+    style.width.unwrap_or(Length::Fixed(DEFAULT_WIDTH))
+}
+
+pub fn view<'a>(data: &ScrollbarNodeData, menu: &IcedMenu) -> Element<'a, Message> {
+    if !is_visible(menu) {
+        return Space::new(Length::Shrink, Length::Shrink).into();
+    }
+    // The thumb's share of the track is the share of matches that are visible
+    let n_visible = menu.visible_items().len();
+    let n_after = menu.matched_items.len() - menu.scroll_offset - n_visible;
+    let portion = |n: usize| Length::FillPortion(n.min(u16::MAX as usize) as u16);
+    let thumb = widget::container(Space::new(Length::Fill, Length::Fill))
+        .width(Length::Fill)
+        .height(portion(n_visible))
+        .style(ContainerTheme::create(data.thumb_style));
+    let track = widget::column(vec![
+        Space::new(Length::Fill, portion(menu.scroll_offset)).into(),
+        thumb.into(),
+        Space::new(Length::Fill, portion(n_after)).into(),
+    ]);
+    let style = &data.style;
+    widget::container(track)
+        .width(bar_width(style))
+        .height(style.height.unwrap_or(Length::Fill))
+        .style(ContainerTheme::create(*style))
+        .into()
+}
+
+pub fn height(data: &ScrollbarNodeData, menu: &IcedMenu) -> u32 {
+    // The scrollbar fills the height of its parent, so it takes no space of its own
+    if !is_visible(menu) {
+        return 0;
+    }
+    let style = &data.style;
+    apply_height_styles!(0, style)
+}
+
+pub fn width(data: &ScrollbarNodeData, menu: &IcedMenu) -> u32 {
+    if !is_visible(menu) {
+        return 0;
+    }
+    let style = &data.style;
+    let width = match bar_width(style) {
+        Length::Fixed(width) => width as u32,
+        _ => DEFAULT_WIDTH as u32,
+    };
+    apply_width_styles!(width, style)
+}
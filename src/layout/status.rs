@@ -0,0 +1,73 @@
+use crate::{apply_height_styles, apply_styles, apply_width_styles};
+use iced::{widget, Element};
+use kdl::KdlNode;
+
+use super::style::GenericStyle;
+use super::LayoutNode;
+use crate::app::{IcedMenu, Message};
+use crate::config::ConfigError;
+
+#[derive(Debug)]
+pub struct StatusNodeData {
+    pub style: GenericStyle,
+}
+
+pub fn new(
+    node: &KdlNode,
+    children: Vec<LayoutNode>,
+    style: GenericStyle,
+) -> Result<LayoutNode, ConfigError> {
+    super::validate_children(node, children.len(), 0)?;
+    Ok(LayoutNode::Status(Box::new(StatusNodeData { style })))
+}
+
+/// Errors take priority over messages
+fn message(menu: &IcedMenu) -> Option<String> {
+    match (&menu.error, &menu.message) {
+        (Some(e), _) => Some(e.to_string()),
+        (None, message) => message.clone(),
+    }
+}
+
+pub fn view<'a>(data: &StatusNodeData, menu: &IcedMenu) -> Element<'a, Message> {
+    let Some(message) = message(menu) else {
+        return widget::Space::new(iced::Length::Shrink, iced::Length::Shrink).into();
+    };
+    let style = &data.style;
+    let result = widget::text(message);
+    apply_styles!(
+        result,
+        style;
+        font,
+        width,
+        height,
+        horizontal_alignment,
+        vertical_alignment;
+        style: text_color,
+        size: font_size,
+    )
+    .into()
+}
+
+pub fn height(data: &StatusNodeData, menu: &IcedMenu) -> u32 {
+    let Some(message) = message(menu) else {
+        return 0;
+    };
+    let style = &data.style;
+    let font = style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32;
+    apply_height_styles!(message.lines().count().max(1) as u32 * font, style)
+}
+
+pub fn width(data: &StatusNodeData, menu: &IcedMenu) -> u32 {
+    let Some(message) = message(menu) else {
+        return 0;
+    };
+    let style = &data.style;
+    let font = style.font_size.unwrap_or(crate::app::DEFAULT_FONT_SIZE) as u32;
+    let longest_line = message
+        .lines()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    apply_width_styles!((longest_line as f32 * 0.7) as u32 * font, style)
+}
@@ -2,11 +2,12 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::iter::once;
 
-use icedmenu::{Reflective, UpdateFromOther};
+use crate::{Reflective, UpdateFromOther};
 use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
 
 use crate::config::ConfigError;
 use crate::font::FontLoader;
+use crate::highlight::SyntaxTheme;
 
 #[derive(Clone, Copy)]
 pub enum State {
@@ -15,6 +16,12 @@ pub enum State {
     Focused,
     Pressed,
     Selected,
+    /// The thumb of a Scrollbar
+    Thumb,
+    /// Vim-style normal mode is active
+    Normal,
+    /// The query was submitted but doesn't match `--validate`
+    Invalid,
 }
 
 impl State {
@@ -25,6 +32,9 @@ impl State {
             Self::Focused => ":focused",
             Self::Pressed => ":pressed",
             Self::Selected => ":selected",
+            Self::Thumb => ":thumb",
+            Self::Normal => ":normal",
+            Self::Invalid => ":invalid",
         }
     }
 }
@@ -51,6 +61,8 @@ pub struct GenericStyle {
     pub icon_color: Option<iced::Color>,
     pub background: Option<iced::Background>,
     pub font: Option<iced::Font>,
+    /// Highlights the output of a Preview command as code, with one of syntect's themes
+    pub syntax_theme: Option<SyntaxTheme>,
 }
 
 impl GenericStyle {
@@ -126,6 +138,19 @@ impl GenericStyle {
                     let font_name = font_loader.ensure_font(child, value_def)?;
                     result.font = Some(font_loader.get(font_name));
                 }
+                "syntax_theme" => {
+                    let name = string_value(child, value_def)?;
+                    result.syntax_theme = Some(SyntaxTheme::from_name(name).ok_or_else(|| {
+                        ConfigError::InvalidValue {
+                            attr_src: *child.span(),
+                            value_src: *value_def.span(),
+                            help: format!(
+                                "`syntax_theme` can be one of: {}",
+                                SyntaxTheme::NAMES.join(", ")
+                            ),
+                        }
+                    })?)
+                }
                 _ => {
                     return Err(ConfigError::InvalidStyleAttribute {
                         attr_src: *child.span(),
@@ -164,7 +189,7 @@ fn int_attr(
     })
 }
 
-fn float_attr(
+pub fn float_attr(
     attribute_definition: &KdlNode,
     value_definition: &KdlEntry,
 ) -> Result<f32, ConfigError> {
@@ -226,6 +251,42 @@ pub fn string_value<'a>(
     })
 }
 
+/// A string property of a layout node, like `name="description"`, if it has one
+pub(crate) fn string_property(node: &KdlNode, name: &str) -> Result<Option<String>, ConfigError> {
+    let Some(entry) = node.get(name) else {
+        return Ok(None);
+    };
+    match entry.value().as_string() {
+        Some(value) => Ok(Some(value.to_string())),
+        None => {
+            let node_name = node.name().value();
+            Err(ConfigError::InvalidArgument {
+                arg_src: *entry.span(),
+                help: format!(
+                    "The {name} for {node_name} nodes should be a string: `{node_name} {name}=\"...\"`"
+                ),
+            })
+        }
+    }
+}
+
+pub fn bool_value(
+    attribute_definition: &KdlNode,
+    value_definition: &KdlEntry,
+) -> Result<bool, ConfigError> {
+    match value_definition.value() {
+        KdlValue::Bool(v) => Ok(*v),
+        _ => Err(ConfigError::InvalidValue {
+            attr_src: *attribute_definition.name().span(),
+            value_src: *value_definition.span(),
+            help: format!(
+                "The value of a `{}` attribute should be `true` or `false`",
+                attribute_definition.name().value()
+            ),
+        }),
+    }
+}
+
 fn color_attr(
     attribute_definition: &KdlNode,
     value_definition: &KdlEntry,
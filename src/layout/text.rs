@@ -1,11 +1,14 @@
-use iced::{widget, Element};
-use icedmenu::{apply_height_styles, apply_styles, apply_width_styles};
+use crate::{apply_height_styles, apply_styles, apply_width_styles};
+use iced::{widget, Color, Element, Font};
 use kdl::KdlNode;
 
+use super::container::ContainerTheme;
 use super::style::GenericStyle;
 use super::LayoutNode;
+use crate::ansi::Span;
 use crate::app::Message;
 use crate::config::ConfigError;
+use crate::font;
 
 #[derive(Debug)]
 pub struct TextNodeData {
@@ -40,7 +43,7 @@ pub fn new(
     }
 }
 
-pub fn view(data: &TextNodeData) -> Element<Message> {
+pub fn view(data: &TextNodeData) -> Element<'_, Message> {
     let style = &data.style;
     let result = widget::text(&data.value);
     apply_styles!(
@@ -71,3 +74,29 @@ pub fn width(data: &TextNodeData) -> u32 {
         style
     )
 }
+
+/// Shows `text` with the colors and weight of `span`, on top of `font`. `color` takes
+/// priority over the span's own color.
+pub(super) fn span_view<'a>(
+    text: widget::Text<'a>,
+    span: &Span,
+    font: Option<Font>,
+    color: Option<Color>,
+) -> Element<'a, Message> {
+    let mut text = text;
+    if let Some(font) = font::variant(font, span.bold, span.italic) {
+        text = text.font(font);
+    }
+    if let Some(color) = color.or(span.color) {
+        text = text.style(color);
+    }
+    match span.background {
+        Some(background) => widget::container(text)
+            .style(ContainerTheme::create(GenericStyle {
+                background: Some(iced::Background::Color(background)),
+                ..Default::default()
+            }))
+            .into(),
+        None => text.into(),
+    }
+}
@@ -1,4 +1,41 @@
+//! A menu for picking items, like dmenu and rofi, configured with KDL. Besides the
+//! `icedmenu` command, [`MenuBuilder`] shows a menu from Rust code. Only one menu can be
+//! shown per process, and only from the main thread, because of the limits of winit.
+
+mod ansi;
+mod app;
+mod callback;
+pub mod cli;
+mod columns;
+mod config;
+mod editor;
+mod error;
+mod exit;
+pub mod filter;
+mod font;
+mod highlight;
+mod icon;
+mod input;
+mod item;
+mod keybindings;
+mod layout;
+mod markup;
+mod matcher;
+mod menu;
+pub mod output;
+mod preview;
+mod provider;
+pub mod render;
+mod search;
+
+pub use app::{Flags, IcedMenu};
+pub use error::MenuError;
+pub use exit::Exit;
 pub use icedmenu_derive::{Reflective, UpdateFromOther};
+pub use item::{Item, ItemData};
+pub use layout::LayoutNode;
+pub use menu::{IntoItem, MenuBuilder, MenuOptions, Selection};
+pub use search::SearchField;
 
 pub trait Reflective {
     fn reflect_attr_names() -> Vec<&'static str>;
@@ -11,7 +48,10 @@ pub trait UpdateFromOther {
 #[macro_export]
 macro_rules! get_item_style {
     ($item:ident, $item_data:ident, $menu:ident) => {
-        match ($menu.index_under_cursor() == $item.index, $item.selected) {
+        match (
+            $menu.index_under_cursor() == Some($item.index),
+            $item.selected,
+        ) {
             (true, true) => {
                 let mut s: GenericStyle = $item_data.selected_style;
                 s.update_from(&$item_data.hovered_style);
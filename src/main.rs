@@ -1,34 +1,29 @@
-mod app;
-mod callback;
-mod cli;
-mod config;
-mod font;
-mod item;
-mod layout;
-
-use crate::app::{Flags, IcedMenu};
 use clap::Parser;
-use iced::{window, Application, Settings};
-
-use self::app::DEFAULT_FONT_SIZE;
+use icedmenu::cli::CliArgs;
+use icedmenu::output::Output;
+use icedmenu::{filter, render, Exit, Flags, MenuBuilder, MenuOptions};
+use miette::IntoDiagnostic;
 
-fn main() -> iced::Result {
-    let cli_args = cli::CliArgs::parse();
-    let flags = Flags::new(cli_args);
-
-    // Get input from stdin
-    let window = window::Settings {
-        decorations: false,
-        resizable: false,
-        transparent: true,
-        always_on_top: true,
-        max_size: None,
-        ..Default::default()
-    };
+fn main() {
+    let cli_args = CliArgs::parse();
+    if let Err(e) = run(cli_args) {
+        eprintln!("{e:?}");
+        Exit::Error.exit();
+    }
+}
 
-    // Display app
-    let mut settings = Settings::with_flags(flags);
-    settings.window = window;
-    settings.default_text_size = DEFAULT_FONT_SIZE;
-    IcedMenu::run(settings)
+fn run(cli_args: CliArgs) -> miette::Result<()> {
+    let output = Output::new(&cli_args);
+    let filter = cli_args.filter.clone();
+    let render_to_png = cli_args.render_to_png.clone();
+    let options = MenuOptions::from(cli_args);
+    if let Some(query) = filter {
+        filter::run(Flags::new(options)?, &query, &output);
+    }
+    if let Some(path) = render_to_png {
+        render::run(Flags::new(options)?, &path);
+    }
+    let selection = MenuBuilder::from_options(options).run()?;
+    output.print(&selection).into_diagnostic()?;
+    selection.exit.exit()
 }
@@ -0,0 +1,265 @@
+use iced::Color;
+
+use crate::ansi::{self, Line, Span};
+
+/// How the colors and weights of keys are given in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMarkup {
+    /// ANSI escape sequences, as printed by `ls --color=always`
+    Ansi,
+    /// A subset of Pango markup, as used by rofi
+    Pango,
+}
+
+impl KeyMarkup {
+    pub fn new(ansi: bool, markup: bool) -> Option<Self> {
+        match (ansi, markup) {
+            (true, _) => Some(Self::Ansi),
+            (_, true) => Some(Self::Pango),
+            _ => None,
+        }
+    }
+
+    /// Splits `key` into styled spans. Their text is the key that is matched and output,
+    /// so only the markup itself is removed.
+    pub fn parse(self, key: &str) -> Line {
+        match self {
+            Self::Ansi => ansi::parse_raw(key),
+            Self::Pango => parse(key),
+        }
+    }
+}
+
+/// The text of `spans` without any styles
+pub fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+/// Parses `<b>`, `<i>` and `<span>` tags with `color`, `background`, `weight` and `style`
+/// attributes, and entities like `&amp;`. Other tags are dropped, keeping their text. Text
+/// that isn't valid markup is kept as it is.
+pub fn parse(text: &str) -> Line {
+    let mut spans = Vec::new();
+    // The styles of the open tags, with the text's own style at the bottom
+    let mut styles = vec![Span::default()];
+    let mut span = Span::default();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let is_tag_start = |c: char| c.is_ascii_alphabetic() || c == '/';
+        let tag = match c {
+            '<' if rest[1..].starts_with(is_tag_start) => {
+                rest.find('>').map(|end| (&rest[1..end], end + 1))
+            }
+            _ => None,
+        };
+        if let Some((tag, len)) = tag {
+            rest = &rest[len..];
+            let style = styles.last().cloned().unwrap_or_default();
+            let new_style = match tag.strip_prefix('/') {
+                // The text's own style can't be closed
+                Some(_) if styles.len() > 1 => styles.pop().and(styles.last().cloned()),
+                Some(_) => None,
+                // Self-closing tags like `<br/>` have no text to style
+                None if tag.ends_with('/') => None,
+                None => {
+                    let style = apply_tag(style, tag);
+                    styles.push(style.clone());
+                    Some(style)
+                }
+            };
+            if let Some(style) = new_style {
+                let next = Span {
+                    text: String::new(),
+                    ..style
+                };
+                let ended = std::mem::replace(&mut span, next);
+                if !ended.text.is_empty() {
+                    spans.push(ended);
+                }
+            }
+            continue;
+        }
+        let (decoded, len) = match c {
+            '&' => entity(rest).unwrap_or(('&', 1)),
+            c => (c, c.len_utf8()),
+        };
+        span.text.push(decoded);
+        rest = &rest[len..];
+    }
+    if !span.text.is_empty() {
+        spans.push(span);
+    }
+    spans
+}
+
+fn apply_tag(mut style: Span, tag: &str) -> Span {
+    let mut parts = tag.splitn(2, char::is_whitespace);
+    match parts.next().unwrap_or_default() {
+        "b" => style.bold = true,
+        "i" => style.italic = true,
+        "span" => {
+            for (name, value) in attributes(parts.next().unwrap_or_default()) {
+                match name {
+                    "color" | "foreground" | "fgcolor" => style.color = color(value),
+                    "background" | "bgcolor" => style.background = color(value),
+                    "weight" | "font_weight" => style.bold = matches!(value, "bold" | "heavy"),
+                    "style" | "font_style" => style.italic = matches!(value, "italic" | "oblique"),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    style
+}
+
+/// Reads attributes like `color="red" weight='bold'`
+fn attributes(mut text: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    while let Some((name, rest)) = text.split_once('=') {
+        let rest = rest.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = rest[1..].find(quote) else {
+            break;
+        };
+        result.push((name.trim(), &rest[1..end + 1]));
+        text = &rest[end + 2..];
+    }
+    result
+}
+
+fn color(value: &str) -> Option<Color> {
+    let [r, g, b, a] = csscolorparser::parse(value).ok()?.to_rgba8();
+    Some(Color::from_rgba8(r, g, b, a as f32 / 255.0))
+}
+
+/// Decodes an entity like `&lt;` or `&#x263A;` at the start of `text`, returning the
+/// character and the length of the entity
+fn entity(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';')?;
+    let c = match &text[1..end] {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "quot" => '"',
+        "apos" => '\'',
+        name => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((c, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str) -> Span {
+        Span {
+            text: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn bold(text: &str) -> Span {
+        Span {
+            bold: true,
+            ..span(text)
+        }
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            parse("a<b>b<i>c</i></b>d"),
+            [
+                span("a"),
+                bold("b"),
+                Span {
+                    italic: true,
+                    ..bold("c")
+                },
+                span("d"),
+            ]
+        );
+        // Other tags keep their text
+        let other = parse("<u>a</u><br/>b");
+        assert_eq!(plain_text(&other), "ab");
+        assert!(other
+            .iter()
+            .all(|s| s.color.is_none() && !s.bold && !s.italic));
+        // Closing tags that weren't opened are dropped
+        assert_eq!(parse("a</b><b>b</i>c"), [span("a"), bold("b"), span("c")]);
+        assert_eq!(parse("<b>a"), [bold("a")]);
+    }
+
+    #[test]
+    fn span_attributes() {
+        assert_eq!(
+            parse(r#"<span color="red" background='#00ff0080' weight="bold">a</span>"#),
+            [Span {
+                color: Some(Color::from_rgb8(255, 0, 0)),
+                background: Some(Color::from_rgba8(0, 255, 0, 128.0 / 255.0)),
+                ..bold("a")
+            }]
+        );
+        assert_eq!(
+            parse(r#"<b><span fgcolor="rgb(1, 2, 3)" font_weight="normal" style="italic">a"#),
+            [Span {
+                color: Some(Color::from_rgb8(1, 2, 3)),
+                italic: true,
+                ..span("a")
+            }]
+        );
+        // Invalid attributes are ignored
+        assert_eq!(
+            parse(r#"<span color="nope" size="large" weight=bold>a</span>"#),
+            [span("a")]
+        );
+    }
+
+    #[test]
+    fn text_that_isnt_markup() {
+        assert_eq!(parse("a < b > c"), [span("a < b > c")]);
+        assert_eq!(parse("1<2"), [span("1<2")]);
+        assert_eq!(parse("<b"), [span("<b")]);
+        assert_eq!(parse("\ta\n"), [span("\ta\n")]);
+        assert_eq!(parse(""), []);
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(parse("&lt;b&gt; &amp;&quot;&apos;"), [span("<b> &\"'")]);
+        assert_eq!(parse("&#9786;&#x263A;&#X263a;"), [span("☺☺☺")]);
+        assert_eq!(
+            parse("a & b; &nope; &#xZZ; &#;"),
+            [span("a & b; &nope; &#xZZ; &#;")]
+        );
+        assert_eq!(parse("&amp"), [span("&amp")]);
+    }
+
+    #[test]
+    fn entity_lengths() {
+        assert_eq!(entity("&lt;rest"), Some(('<', 4)));
+        assert_eq!(entity("&#65;"), Some(('A', 5)));
+        assert_eq!(entity("&#x1F600;"), Some(('😀', 9)));
+        // Surrogates and numbers past the last character aren't characters
+        assert_eq!(entity("&#xD800;"), None);
+        assert_eq!(entity("&#x110000;"), None);
+        assert_eq!(entity("&#-1;"), None);
+        assert_eq!(entity("&lt"), None);
+    }
+
+    #[test]
+    fn ansi_keys_keep_their_text() {
+        assert_eq!(KeyMarkup::Ansi.parse("\x1b[1ma\tb\x1b[0m"), [bold("a\tb")]);
+        assert_eq!(plain_text(&KeyMarkup::Pango.parse("<b>a</b>&amp;b")), "a&b");
+    }
+}
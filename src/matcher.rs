@@ -0,0 +1,420 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use regex::{Regex, RegexBuilder};
+use std::sync::Mutex;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::cli::{CaseSensitivity, MatchingAlgorithm};
+
+/// The score given to an exact match, which the other algorithms score relative to
+const MAX_SCORE: u32 = 10_000;
+
+pub trait Matcher: Send + Sync {
+    /// Scores `haystack` against `needle`, returning `None` if they do not match.
+    /// The indices are the positions of the matched characters in `haystack` (in chars, not bytes).
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)>;
+}
+
+/// Creates a matcher. If `normalize` is set, diacritics are ignored, so `cafe` matches `Café`.
+pub fn new(
+    algorithm: MatchingAlgorithm,
+    case: CaseSensitivity,
+    normalize: bool,
+) -> Box<dyn Matcher> {
+    let matcher = with_algorithm(algorithm, case);
+    if normalize {
+        Box::new(Normalized { matcher })
+    } else {
+        matcher
+    }
+}
+
+fn with_algorithm(algorithm: MatchingAlgorithm, case: CaseSensitivity) -> Box<dyn Matcher> {
+    match algorithm {
+        MatchingAlgorithm::Fuzzy => Box::new(Fuzzy::new(case)),
+        MatchingAlgorithm::Exact => Box::new(Exact { case }),
+        MatchingAlgorithm::Prefix => Box::new(Prefix { case }),
+        MatchingAlgorithm::Suffix => Box::new(Suffix { case }),
+        MatchingAlgorithm::Substring => Box::new(Substring { case }),
+        MatchingAlgorithm::WordPrefix => Box::new(WordPrefix { case }),
+        MatchingAlgorithm::Regex => Box::new(RegexMatcher {
+            case,
+            cache: Mutex::new(None),
+        }),
+    }
+}
+
+fn ignore_case(case: CaseSensitivity, needle: &str) -> bool {
+    match case {
+        CaseSensitivity::Smart => !needle.chars().any(char::is_uppercase),
+        CaseSensitivity::Respect => false,
+        CaseSensitivity::Ignore => true,
+    }
+}
+
+/// Like `ignore_case`, but escapes like `\W`, `\S` or `\p{Lu}` don't count as uppercase
+fn regex_ignore_case(case: CaseSensitivity, pattern: &str) -> bool {
+    if case != CaseSensitivity::Smart {
+        return ignore_case(case, pattern);
+    }
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            if c.is_uppercase() {
+                return false;
+            }
+            continue;
+        }
+        match chars.next() {
+            // Unicode classes like `\pL` or `\p{Lu}`, and hex escapes like `\x{1F600}`
+            Some('p' | 'P' | 'x' | 'u' | 'U') if chars.peek() == Some(&'{') => {
+                chars.by_ref().find(|&c| c == '}');
+            }
+            Some('p' | 'P') => {
+                chars.next();
+            }
+            Some('x') => {
+                chars.next();
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+fn chars_eq(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+/// Whether `needle` occurs in `haystack` starting at char position `start`
+fn matches_at(haystack: &[char], needle: &[char], start: usize, ignore_case: bool) -> bool {
+    haystack.len() >= start + needle.len()
+        && haystack[start..]
+            .iter()
+            .zip(needle)
+            .all(|(&a, &b)| chars_eq(a, b, ignore_case))
+}
+
+fn is_word_start(haystack: &[char], i: usize) -> bool {
+    i == 0 || !haystack[i - 1].is_alphanumeric()
+}
+
+/// Scores a literal match, preferring matches near the start of short items
+fn literal_score(haystack_len: usize, start: usize) -> u32 {
+    (MAX_SCORE / 2).saturating_sub((start * 4 + haystack_len) as u32)
+}
+
+pub struct Fuzzy {
+    matcher: SkimMatcherV2,
+}
+
+impl Fuzzy {
+    fn new(case: CaseSensitivity) -> Self {
+        let matcher = SkimMatcherV2::default();
+        Self {
+            matcher: match case {
+                CaseSensitivity::Smart => matcher.smart_case(),
+                CaseSensitivity::Respect => matcher.respect_case(),
+                CaseSensitivity::Ignore => matcher.ignore_case(),
+            },
+        }
+    }
+}
+
+impl Matcher for Fuzzy {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        self.matcher
+            .fuzzy_indices(haystack, needle)
+            .map(|(score, indices)| (score.max(0) as u32, indices))
+    }
+}
+
+/// Matches items that are equal to the query
+pub struct Exact {
+    case: CaseSensitivity,
+}
+
+impl Matcher for Exact {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let ignore_case = ignore_case(self.case, needle);
+        let haystack: Vec<char> = haystack.chars().collect();
+        let needle: Vec<char> = needle.chars().collect();
+        (haystack.len() == needle.len() && matches_at(&haystack, &needle, 0, ignore_case))
+            .then(|| (MAX_SCORE, (0..haystack.len()).collect()))
+    }
+}
+
+/// Matches items that start with the query
+pub struct Prefix {
+    case: CaseSensitivity,
+}
+
+impl Matcher for Prefix {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let ignore_case = ignore_case(self.case, needle);
+        let haystack: Vec<char> = haystack.chars().collect();
+        let needle: Vec<char> = needle.chars().collect();
+        matches_at(&haystack, &needle, 0, ignore_case).then(|| {
+            (
+                literal_score(haystack.len(), 0),
+                (0..needle.len()).collect(),
+            )
+        })
+    }
+}
+
+/// Matches items that end with the query
+pub struct Suffix {
+    case: CaseSensitivity,
+}
+
+impl Matcher for Suffix {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let ignore_case = ignore_case(self.case, needle);
+        let haystack: Vec<char> = haystack.chars().collect();
+        let needle: Vec<char> = needle.chars().collect();
+        let start = haystack.len().checked_sub(needle.len())?;
+        matches_at(&haystack, &needle, start, ignore_case).then(|| {
+            (
+                literal_score(haystack.len(), 0),
+                (start..haystack.len()).collect(),
+            )
+        })
+    }
+}
+
+/// Matches items that contain the query
+pub struct Substring {
+    case: CaseSensitivity,
+}
+
+impl Matcher for Substring {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let ignore_case = ignore_case(self.case, needle);
+        let haystack: Vec<char> = haystack.chars().collect();
+        let needle: Vec<char> = needle.chars().collect();
+        (0..=haystack.len().saturating_sub(needle.len()))
+            .find(|&start| matches_at(&haystack, &needle, start, ignore_case))
+            .map(|start| {
+                (
+                    literal_score(haystack.len(), start),
+                    (start..start + needle.len()).collect(),
+                )
+            })
+    }
+}
+
+/// Matches items that contain a word starting with the query
+pub struct WordPrefix {
+    case: CaseSensitivity,
+}
+
+impl Matcher for WordPrefix {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let ignore_case = ignore_case(self.case, needle);
+        let haystack: Vec<char> = haystack.chars().collect();
+        let needle: Vec<char> = needle.chars().collect();
+        (0..=haystack.len().saturating_sub(needle.len()))
+            .filter(|&start| is_word_start(&haystack, start))
+            .find(|&start| matches_at(&haystack, &needle, start, ignore_case))
+            .map(|start| {
+                (
+                    literal_score(haystack.len(), start),
+                    (start..start + needle.len()).collect(),
+                )
+            })
+    }
+}
+
+/// Matches items against the query as a regular expression
+pub struct RegexMatcher {
+    case: CaseSensitivity,
+    /// The most recently compiled query, since every item is matched against the same one
+    cache: Mutex<Option<(String, Option<Regex>)>>,
+}
+
+impl Matcher for RegexMatcher {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let mut cache = self.cache.lock().unwrap();
+        if !matches!(&*cache, Some((pattern, _)) if pattern == needle) {
+            // An incomplete pattern while typing simply matches nothing
+            let regex = RegexBuilder::new(needle)
+                .case_insensitive(regex_ignore_case(self.case, needle))
+                .build()
+                .ok();
+            *cache = Some((needle.to_owned(), regex));
+        }
+        let regex = cache.as_ref()?.1.as_ref()?;
+        let found = regex.find(haystack)?;
+        let start = haystack[..found.start()].chars().count();
+        let len = found.as_str().chars().count();
+        Some((
+            literal_score(haystack.chars().count(), start),
+            (start..start + len).collect(),
+        ))
+    }
+}
+
+/// Decomposes `text` into its compatibility form (NFKD) without combining marks. Returns the
+/// folded text along with the position in `text` of each of its chars.
+fn fold(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut positions = Vec::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        for d in std::iter::once(c).nfkd().filter(|&d| !is_combining_mark(d)) {
+            folded.push(d);
+            positions.push(i);
+        }
+    }
+    (folded, positions)
+}
+
+/// Matches the folded forms of the haystack and needle with another matcher
+pub struct Normalized {
+    matcher: Box<dyn Matcher>,
+}
+
+impl Matcher for Normalized {
+    fn match_indices(&self, haystack: &str, needle: &str) -> Option<(u32, Vec<usize>)> {
+        let (folded_haystack, positions) = fold(haystack);
+        let (folded_needle, _) = fold(needle);
+        let (score, indices) = self
+            .matcher
+            .match_indices(&folded_haystack, &folded_needle)?;
+        let mut indices: Vec<usize> = indices.into_iter().map(|i| positions[i]).collect();
+        // Several folded chars can come from the same original one, like `ﬁ`
+        indices.dedup();
+        Some((score, indices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(
+        algorithm: MatchingAlgorithm,
+        case: CaseSensitivity,
+        haystack: &str,
+        needle: &str,
+    ) -> Option<(u32, Vec<usize>)> {
+        new(algorithm, case, false).match_indices(haystack, needle)
+    }
+
+    fn indices(algorithm: MatchingAlgorithm, haystack: &str, needle: &str) -> Option<Vec<usize>> {
+        matches(algorithm, CaseSensitivity::Smart, haystack, needle).map(|(_, indices)| indices)
+    }
+
+    fn score(algorithm: MatchingAlgorithm, haystack: &str, needle: &str) -> u32 {
+        matches(algorithm, CaseSensitivity::Smart, haystack, needle)
+            .expect("should match")
+            .0
+    }
+
+    #[test]
+    fn fuzzy() {
+        use MatchingAlgorithm::Fuzzy;
+        assert_eq!(indices(Fuzzy, "foo bar", "fb"), Some(vec![0, 4]));
+        assert_eq!(indices(Fuzzy, "foo bar", "bf"), None);
+        // Consecutive matches score higher than scattered ones
+        assert!(score(Fuzzy, "abc", "abc") > score(Fuzzy, "a-b-c", "abc"));
+    }
+
+    #[test]
+    fn exact() {
+        use MatchingAlgorithm::Exact;
+        assert_eq!(indices(Exact, "abc", "abc"), Some(vec![0, 1, 2]));
+        assert_eq!(indices(Exact, "abcd", "abc"), None);
+        assert_eq!(score(Exact, "abc", "abc"), MAX_SCORE);
+    }
+
+    #[test]
+    fn prefix() {
+        use MatchingAlgorithm::Prefix;
+        assert_eq!(indices(Prefix, "foobar", "foo"), Some(vec![0, 1, 2]));
+        assert_eq!(indices(Prefix, "barfoo", "foo"), None);
+        // Shorter items are closer to what was typed
+        assert!(score(Prefix, "foo", "foo") > score(Prefix, "foobar", "foo"));
+    }
+
+    #[test]
+    fn suffix() {
+        use MatchingAlgorithm::Suffix;
+        assert_eq!(indices(Suffix, "barfoo", "foo"), Some(vec![3, 4, 5]));
+        assert_eq!(indices(Suffix, "foobar", "foo"), None);
+        assert_eq!(indices(Suffix, "fo", "foo"), None);
+    }
+
+    #[test]
+    fn substring() {
+        use MatchingAlgorithm::Substring;
+        assert_eq!(indices(Substring, "a foo", "foo"), Some(vec![2, 3, 4]));
+        assert_eq!(indices(Substring, "f-o-o", "foo"), None);
+        // Matches near the start score higher
+        assert!(score(Substring, "foo x", "foo") > score(Substring, "x foo", "foo"));
+    }
+
+    #[test]
+    fn word_prefix() {
+        use MatchingAlgorithm::WordPrefix;
+        assert_eq!(indices(WordPrefix, "foo bar", "ba"), Some(vec![4, 5]));
+        assert_eq!(indices(WordPrefix, "foo-bar", "bar"), Some(vec![4, 5, 6]));
+        assert_eq!(indices(WordPrefix, "foo bar", "ar"), None);
+    }
+
+    #[test]
+    fn regex() {
+        use MatchingAlgorithm::Regex;
+        assert_eq!(indices(Regex, "foo bar", "b.r"), Some(vec![4, 5, 6]));
+        // Indices are in chars, not bytes
+        assert_eq!(indices(Regex, "été", "t."), Some(vec![1, 2]));
+        // Incomplete patterns match nothing instead of failing
+        assert_eq!(indices(Regex, "(foo", "(foo"), None);
+    }
+
+    #[test]
+    fn smart_case() {
+        use MatchingAlgorithm::{Regex, Substring};
+        assert!(indices(Substring, "FOO", "foo").is_some());
+        assert!(indices(Substring, "foo", "Foo").is_none());
+        assert!(indices(Substring, "Foo", "Foo").is_some());
+        assert!(indices(Regex, "foo", "Foo").is_none());
+        // Escapes are not uppercase letters of the pattern
+        assert!(indices(Regex, "FOO BAR", r"foo\Wbar").is_some());
+        assert!(indices(Regex, "FOO BAR", r"\S+\s\D+").is_some());
+        assert!(indices(Regex, "FOO", r"\p{Lu}oo").is_some());
+        assert!(indices(Regex, "Ab", r"\x41b").is_some());
+        assert!(indices(Regex, "ab", r"\x41B").is_none());
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        use CaseSensitivity::{Ignore, Respect};
+        use MatchingAlgorithm::{Prefix, Regex};
+        assert!(matches(Prefix, Respect, "Foo", "foo").is_none());
+        assert!(matches(Prefix, Ignore, "foo", "FOO").is_some());
+        assert!(matches(Regex, Respect, "FOO", r"\w+").is_some());
+        assert!(matches(Regex, Respect, "FOO", "foo").is_none());
+    }
+
+    #[test]
+    fn normalized() {
+        let matcher = new(MatchingAlgorithm::Substring, CaseSensitivity::Smart, true);
+        assert_eq!(
+            matcher.match_indices("Café", "cafe"),
+            Some((literal_score(4, 0), vec![0, 1, 2, 3]))
+        );
+        // `ﬁ` folds into two chars, which map back to the same one
+        assert_eq!(
+            matcher.match_indices("ﬁle", "file").map(|(_, i)| i),
+            Some(vec![0, 1, 2])
+        );
+        assert_eq!(
+            new(MatchingAlgorithm::Substring, CaseSensitivity::Smart, false)
+                .match_indices("Café", "cafe"),
+            None
+        );
+    }
+}
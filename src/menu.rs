@@ -0,0 +1,317 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use iced::{window, Application};
+use miette::IntoDiagnostic;
+use regex::Regex;
+
+use crate::app::{Flags, IcedMenu, DEFAULT_FONT_SIZE};
+use crate::cli::{CaseSensitivity, CustomPolicy, InputFormat, MatchingAlgorithm, SelectionOrder};
+use crate::columns::ColumnRanges;
+use crate::error::MenuError;
+use crate::exit::Exit;
+use crate::item::{Item, ItemData};
+use crate::search::SearchField;
+
+/// Anything that can be shown as an item in the menu
+pub trait IntoItem {
+    fn into_item(self) -> ItemData;
+}
+
+impl IntoItem for ItemData {
+    fn into_item(self) -> ItemData {
+        self
+    }
+}
+
+impl IntoItem for String {
+    fn into_item(self) -> ItemData {
+        ItemData::new(self, None)
+    }
+}
+
+impl IntoItem for &str {
+    fn into_item(self) -> ItemData {
+        ItemData::new(self.to_owned(), None)
+    }
+}
+
+/// A key that is displayed and searched, and a value that is submitted
+impl<K: Into<String>, V: Into<String>> IntoItem for (K, V) {
+    fn into_item(self) -> ItemData {
+        ItemData::new(self.0.into(), Some(self.1.into()))
+    }
+}
+
+/// What the menu was closed with
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub exit: Exit,
+    /// The submitted items, in the order they were selected, or in the order of the items
+    /// with `SelectionOrder::Input`
+    pub items: Vec<Item>,
+    /// The query when the menu was closed, which is the result for `Exit::AcceptQuery`
+    pub query: String,
+    /// The keys that closed the menu, like `enter` or `alt+1`. Empty when an item is clicked.
+    pub keys: String,
+}
+
+/// Everything about how a menu behaves. These are the command line options, except for
+/// the ones about output, which `CliArgs` converts into.
+#[derive(Debug, Clone)]
+pub struct MenuOptions {
+    pub prompt: String,
+    /// An initial value for the query
+    pub query: String,
+    /// Read items from a file instead of stdin
+    pub file: Option<PathBuf>,
+    pub input_format: InputFormat,
+    /// Items are separated by NUL characters instead of newlines
+    pub read0: bool,
+    /// The field delimiter for delimited and CSV input, and for columns
+    pub delimiter: Option<char>,
+    /// A KDL file with the layout, styles, settings and keybindings
+    pub config: Option<PathBuf>,
+    /// Overrides the `matching` setting of the config, which is fuzzy by default
+    pub matching: Option<MatchingAlgorithm>,
+    /// Overrides the `search_field` setting of the config, which is the key by default
+    pub search_fields: Vec<SearchField>,
+    /// Only search these columns of each key
+    pub nth: Option<ColumnRanges>,
+    /// Only display these columns of each key
+    pub with_nth: Option<ColumnRanges>,
+    pub case: CaseSensitivity,
+    /// Ignore diacritics when matching
+    pub normalize: bool,
+    /// Show ANSI escape sequences in keys
+    pub ansi: bool,
+    /// Show Pango markup in keys
+    pub markup: bool,
+    /// The maximum number of items that can be selected
+    pub max: usize,
+    /// The maximum number of items that can be displayed at once
+    pub max_visible: usize,
+    /// The order of the items in a `Selection`
+    pub selection_order: SelectionOrder,
+    /// Use vim-style modes
+    pub vim: bool,
+    /// When the query itself can be submitted instead of an item
+    pub allow_custom: CustomPolicy,
+    /// Only allow submitting a query that matches this
+    pub validate: Option<Regex>,
+    /// How long to wait after the query changes before running the callback
+    pub debounce: Duration,
+    /// Run the callback once as a long-lived provider
+    pub provider: bool,
+    /// A command to run for every query, or the provider
+    pub callback: Option<Vec<String>>,
+}
+
+impl Default for MenuOptions {
+    fn default() -> Self {
+        Self {
+            prompt: String::new(),
+            query: String::new(),
+            file: None,
+            input_format: InputFormat::Lines,
+            read0: false,
+            delimiter: None,
+            config: None,
+            matching: None,
+            search_fields: Vec::new(),
+            nth: None,
+            with_nth: None,
+            case: CaseSensitivity::Smart,
+            normalize: false,
+            ansi: false,
+            markup: false,
+            max: 1,
+            max_visible: 10,
+            selection_order: SelectionOrder::Input,
+            vim: false,
+            allow_custom: CustomPolicy::Explicit,
+            validate: None,
+            debounce: Duration::from_millis(50),
+            provider: false,
+            callback: None,
+        }
+    }
+}
+
+/// Set when a menu creates its event loop, which winit only allows once per process
+static EVENT_LOOP_CREATED: AtomicBool = AtomicBool::new(false);
+
+/// Filled in by the menu right before its window closes
+pub(crate) type SharedOutcome = Arc<Mutex<Option<Result<Selection, MenuError>>>>;
+
+/// Shows a menu from Rust code, with the same options as the command line
+///
+/// ```no_run
+/// let selection = icedmenu::MenuBuilder::new()
+///     .prompt("Fruit")
+///     .items(["apple", "banana", "cherry"])
+///     .run()?;
+/// # Ok::<(), miette::Report>(())
+/// ```
+pub struct MenuBuilder {
+    options: MenuOptions,
+    /// Items given directly, instead of being read from `--file` or stdin
+    items: Option<Vec<ItemData>>,
+    /// A KDL config given directly, instead of with `--config`
+    config: Option<String>,
+}
+
+impl Default for MenuBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MenuBuilder {
+    /// A menu with no items, the default config and the default of every option
+    pub fn new() -> Self {
+        Self {
+            items: Some(Vec::new()),
+            ..Self::from_options(MenuOptions::default())
+        }
+    }
+
+    /// A menu with these options, like `CliArgs` converted into them. Unless `items` is
+    /// used, items are read from the file or stdin, or come from the callback, as for the
+    /// `icedmenu` command.
+    pub fn from_options(options: MenuOptions) -> Self {
+        Self {
+            options,
+            items: None,
+            config: None,
+        }
+    }
+
+    pub fn items<T: IntoItem>(mut self, items: impl IntoIterator<Item = T>) -> Self {
+        self.items = Some(items.into_iter().map(IntoItem::into_item).collect());
+        self
+    }
+
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.options.prompt = prompt.into();
+        self
+    }
+
+    /// An initial value for the query
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.options.query = query.into();
+        self
+    }
+
+    /// Uses KDL source for the layout, styles, settings and keybindings
+    pub fn config(mut self, source: impl Into<String>) -> Self {
+        self.config = Some(source.into());
+        self
+    }
+
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.config = Some(path.into());
+        self.config = None;
+        self
+    }
+
+    pub fn matching(mut self, algorithm: MatchingAlgorithm) -> Self {
+        self.options.matching = Some(algorithm);
+        self
+    }
+
+    pub fn case(mut self, case: CaseSensitivity) -> Self {
+        self.options.case = case;
+        self
+    }
+
+    /// Searches these item fields instead of just the key
+    pub fn search_fields(mut self, fields: Vec<SearchField>) -> Self {
+        self.options.search_fields = fields;
+        self
+    }
+
+    /// Ignores diacritics when matching
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.options.normalize = normalize;
+        self
+    }
+
+    /// The maximum number of items that can be selected
+    pub fn max_selected(mut self, max: usize) -> Self {
+        self.options.max = max;
+        self
+    }
+
+    /// The maximum number of items that are displayed at once
+    pub fn max_visible(mut self, max_visible: usize) -> Self {
+        self.options.max_visible = max_visible;
+        self
+    }
+
+    /// Whether the selected items are in the order they were selected, or in the order of
+    /// the items
+    pub fn selection_order(mut self, order: SelectionOrder) -> Self {
+        self.options.selection_order = order;
+        self
+    }
+
+    /// Uses vim-style modes
+    pub fn vim(mut self, vim: bool) -> Self {
+        self.options.vim = vim;
+        self
+    }
+
+    /// Shows the menu and waits until it is closed. Fails if the config is invalid, or if
+    /// an error happens with the `fatal_errors` setting.
+    ///
+    /// Only one menu can be shown per process, and only from the main thread, because that
+    /// is all winit allows on every platform. Other calls fail instead of showing a menu.
+    pub fn run(self) -> miette::Result<Selection> {
+        if std::thread::current().name() != Some("main") {
+            miette::bail!("A menu can only be shown from the main thread");
+        }
+        let mut flags = match self.config {
+            Some(source) => Flags::with_config(self.options, "(config)", source)?,
+            None => Flags::new(self.options)?,
+        };
+        if let Some(items) = self.items {
+            flags.items = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, data)| Item::new(i, data))
+                .collect();
+            flags.stream_input = false;
+        }
+        let outcome = flags.outcome.clone();
+
+        let mut settings = iced::Settings::with_flags(flags);
+        settings.window = window::Settings {
+            decorations: false,
+            resizable: false,
+            transparent: true,
+            always_on_top: true,
+            max_size: None,
+            ..Default::default()
+        };
+        settings.default_text_size = DEFAULT_FONT_SIZE;
+        if EVENT_LOOP_CREATED.swap(true, Ordering::SeqCst) {
+            miette::bail!("Only one menu can be shown per process");
+        }
+        IcedMenu::run(settings).into_diagnostic()?;
+
+        let outcome = outcome.lock().unwrap().take();
+        match outcome {
+            Some(result) => result.into_diagnostic(),
+            // The window was closed by something other than the menu
+            None => Ok(Selection {
+                exit: Exit::Cancel,
+                items: Vec::new(),
+                query: String::new(),
+                keys: String::new(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::cli::CliArgs;
+use crate::exit::Exit;
+use crate::item::{Item, ItemData};
+use crate::menu::Selection;
+
+/// How each submitted item is written to stdout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The key of the item
+    Key,
+    /// The value of the item, or its key if it has none
+    Value,
+    /// Like `value`, but separated by NUL characters instead of newlines, for `xargs -0`
+    Nul,
+    /// The position of the item in the input, or in the output of the callback or provider
+    /// that returned it, starting from 0
+    Index,
+    /// One JSON array with an object for each item, including its index
+    Json,
+    /// A CSV record with the index, key and value of each item
+    Csv,
+    /// `{field}` is replaced by that field of the item, or by `{index}`
+    Template(String),
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "key" => Self::Key,
+            "value" => Self::Value,
+            "nul" => Self::Nul,
+            "index" => Self::Index,
+            "json" => Self::Json,
+            "csv" => Self::Csv,
+            _ if s.contains('{') => Self::Template(unescape(s)),
+            _ => return Err(String::from(
+                "expected key, value, nul, index, json, csv or a template like `{index}\\t{key}`",
+            )),
+        })
+    }
+}
+
+/// Replaces `\t`, `\n`, `\0` and `\\` so templates can be written without shell quoting tricks
+fn unescape(template: &str) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn value_or_key(data: &ItemData) -> &str {
+    data.value.as_deref().unwrap_or(&data.key)
+}
+
+fn render_template(template: &str, item: &Item) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        match &rest[1..end] {
+            "index" => result.push_str(&item.input_index.to_string()),
+            // Like the `value` format, so that `{value}` is never empty
+            "value" => result.push_str(value_or_key(&item.data)),
+            name => result.push_str(&item.data.field(name).unwrap_or_default()),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[derive(serde::Serialize)]
+struct JsonItem<'a> {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    match_indices: Option<&'a HashMap<String, Vec<usize>>>,
+    #[serde(flatten)]
+    data: &'a ItemData,
+}
+
+/// Formats match indices like `description:0,1 key:2`
+fn format_match_indices(match_indices: &HashMap<String, Vec<usize>>) -> String {
+    let sorted: BTreeMap<_, _> = match_indices.iter().collect();
+    let fields: Vec<String> = sorted
+        .into_iter()
+        .map(|(field, indices)| {
+            let indices: Vec<String> = indices.iter().map(usize::to_string).collect();
+            format!("{field}:{}", indices.join(","))
+        })
+        .collect();
+    fields.join(" ")
+}
+
+/// Everything about what is printed when the menu is closed
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub separator: u8,
+    pub print_query: bool,
+    pub print_key: bool,
+    /// Include the score and match indices of each item, for `--filter`
+    pub print_scores: bool,
+}
+
+impl Output {
+    pub fn new(cli_args: &CliArgs) -> Self {
+        Self {
+            format: cli_args.output_format.clone(),
+            separator: if cli_args.output_format == OutputFormat::Nul {
+                b'\0'
+            } else {
+                b'\n'
+            },
+            print_query: cli_args.print_query,
+            print_key: cli_args.print_key,
+            print_scores: cli_args.scores,
+        }
+    }
+
+    /// Formats each item as one record, except for JSON where they are all in one
+    pub fn records(&self, items: &[&Item]) -> Vec<String> {
+        let records = self.format_records(items);
+        if !self.print_scores || self.format == OutputFormat::Json {
+            return records;
+        }
+        records
+            .into_iter()
+            .zip(items)
+            .map(|(record, item)| {
+                let score = item.score.map(|s| s.to_string()).unwrap_or_default();
+                let match_indices = format_match_indices(&item.match_indices);
+                format!("{score}\t{match_indices}\t{record}")
+            })
+            .collect()
+    }
+
+    fn format_records(&self, items: &[&Item]) -> Vec<String> {
+        match &self.format {
+            OutputFormat::Key => items.iter().map(|item| item.data.key.clone()).collect(),
+            OutputFormat::Value | OutputFormat::Nul => items
+                .iter()
+                .map(|item| value_or_key(&item.data).to_owned())
+                .collect(),
+            OutputFormat::Index => items
+                .iter()
+                .map(|item| item.input_index.to_string())
+                .collect(),
+            OutputFormat::Template(template) => items
+                .iter()
+                .map(|item| render_template(template, item))
+                .collect(),
+            OutputFormat::Json => {
+                let items: Vec<JsonItem> = items
+                    .iter()
+                    .map(|item| JsonItem {
+                        index: item.input_index,
+                        score: item.score.filter(|_| self.print_scores),
+                        match_indices: self.print_scores.then_some(&item.match_indices),
+                        data: &item.data,
+                    })
+                    .collect();
+                vec![serde_json::to_string(&items).unwrap()]
+            }
+            OutputFormat::Csv => items.iter().map(|item| csv_record(item)).collect(),
+        }
+    }
+
+    /// Prints what the menu was closed with. Nothing is printed when it was cancelled.
+    pub fn print(&self, selection: &Selection) -> io::Result<()> {
+        let records = match selection.exit {
+            Exit::Cancel | Exit::Error => return Ok(()),
+            Exit::AcceptQuery => vec![selection.query.clone()],
+            Exit::Accept | Exit::Custom(_) => {
+                self.records(&selection.items.iter().collect::<Vec<_>>())
+            }
+        };
+        self.write(
+            &mut io::stdout().lock(),
+            &selection.query,
+            &selection.keys,
+            records,
+        )
+    }
+
+    /// Writes `records` separated by the separator, after the query and the keys that
+    /// closed the menu if they were asked for
+    pub fn write(
+        &self,
+        writer: &mut impl Write,
+        query: &str,
+        keys: &str,
+        records: Vec<String>,
+    ) -> io::Result<()> {
+        let header = [(self.print_query, query), (self.print_key, keys)];
+        let header = header
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, line)| line.to_owned());
+        let output: Vec<String> = header.chain(records).collect();
+        writer.write_all(
+            output
+                .join(&(self.separator as char).to_string())
+                .as_bytes(),
+        )
+    }
+}
+
+fn csv_record(item: &Item) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    let value = item.data.value.as_deref().unwrap_or_default();
+    writer
+        .write_record([item.input_index.to_string().as_str(), &item.data.key, value])
+        .unwrap();
+    let mut record = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    record.pop();
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An item that was returned third by a callback, but is now first in the menu
+    fn item() -> Item {
+        let mut item = Item::new(2, ItemData::new(String::from("k"), Some(String::from("v"))));
+        item.index = 0;
+        item
+    }
+
+    fn output(format: &str) -> Output {
+        let format = OutputFormat::parse(format).unwrap();
+        Output {
+            separator: if format == OutputFormat::Nul {
+                b'\0'
+            } else {
+                b'\n'
+            },
+            format,
+            print_query: true,
+            print_key: false,
+            print_scores: false,
+        }
+    }
+
+    /// What is written for two copies of `item()` after the query
+    fn written(format: &str) -> String {
+        let output = output(format);
+        let item = item();
+        let mut written = Vec::new();
+        let records = output.records(&[&item, &item]);
+        output.write(&mut written, "q", "", records).unwrap();
+        String::from_utf8(written).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(written("key"), "q\nk\nk");
+        assert_eq!(written("value"), "q\nv\nv");
+        assert_eq!(written("nul"), "q\0v\0v");
+        assert_eq!(written("index"), "q\n2\n2");
+        assert_eq!(written("csv"), "q\n2,k,v\n2,k,v");
+        assert_eq!(
+            written("json"),
+            r#"q
+[{"index":2,"key":"k","value":"v"},{"index":2,"key":"k","value":"v"}]"#
+        );
+        assert!(OutputFormat::parse("print0").is_err());
+    }
+
+    #[test]
+    fn templates() {
+        let render = |template: &str| render_template(&unescape(template), &item());
+        assert_eq!(render("{index}\\t{key}={value}\\0"), "2\tk=v\0");
+        assert_eq!(render("{description}|{other}"), "|");
+        // Unclosed braces are written once
+        assert_eq!(render("{key} {"), "k {");
+        assert_eq!(render("a{b"), "a{b");
+    }
+}
@@ -0,0 +1,219 @@
+use crate::ansi::{self, Line};
+use crate::app::Message;
+use crate::error::MenuError;
+use crate::highlight::{self, SyntaxTheme};
+use crate::item::ItemData;
+use iced::{subscription, Subscription};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+/// The number of outputs kept before the cache is cleared
+const CACHE_SIZE: usize = 256;
+
+/// Runs the command of a `Preview` node for the item under the cursor
+pub struct Preview {
+    command: String,
+    max_bytes: usize,
+    /// Highlights output without colors of its own as code, by the extension of the item
+    syntax_theme: Option<SyntaxTheme>,
+    /// Outputs by the expanded command, so that moving back to an item shows it instantly.
+    /// Commands that could not be run have their error instead.
+    cache: HashMap<String, Result<Vec<Line>, MenuError>>,
+    /// The expanded command for the item under the cursor
+    current: Option<String>,
+    /// The file extension of the item under the cursor
+    extension: Option<String>,
+    /// The expanded command whose output is shown
+    shown: Option<String>,
+}
+
+impl Preview {
+    pub fn new(command: String, max_bytes: usize, syntax_theme: Option<SyntaxTheme>) -> Self {
+        Self {
+            command,
+            max_bytes,
+            syntax_theme,
+            cache: HashMap::new(),
+            current: None,
+            extension: None,
+            shown: None,
+        }
+    }
+
+    /// Replaces `{key}`, `{value}` and any other `{field}` with that field of the item,
+    /// quoted for the shell. `{value}` is the key for items without a value. Other braces,
+    /// like those of `awk '{print $1}'` or `${VAR}`, are left alone.
+    fn expand(&self, data: &ItemData) -> String {
+        let mut result = String::with_capacity(self.command.len());
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find('{') {
+            let name = rest[start + 1..]
+                .find('}')
+                .map(|len| &rest[start + 1..start + 1 + len])
+                .filter(|name| data.is_field(name));
+            let Some(name) = name else {
+                result.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+                continue;
+            };
+            result.push_str(&rest[..start]);
+            let field = match name {
+                "value" => Some(data.value.as_deref().unwrap_or(&data.key).into()),
+                _ => data.field(name),
+            };
+            result.push_str(&shell_quote(&field.unwrap_or_default()));
+            rest = &rest[start + name.len() + 2..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Moves the preview to `item`. Returns whether the shown output changed.
+    pub fn show(&mut self, item: Option<&ItemData>) -> bool {
+        let command = item.map(|data| self.expand(data));
+        if command == self.current {
+            return false;
+        }
+        self.current = command;
+        self.extension = item
+            .map(|data| data.value.as_deref().unwrap_or(&data.key))
+            .and_then(|path| Path::new(path).extension())
+            .map(|extension| extension.to_string_lossy().into_owned());
+        // The previous output stays until the new one is ready, unless nothing matches
+        if self.current.is_none() || self.is_cached() {
+            self.shown = self.current.clone();
+            return true;
+        }
+        false
+    }
+
+    fn is_cached(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|command| self.cache.contains_key(command))
+    }
+
+    /// Stores the output of `command`, or why it could not be run. Returns whether it is now
+    /// shown.
+    pub fn finished(&mut self, command: String, output: Result<Vec<Line>, MenuError>) -> bool {
+        if self.cache.len() >= CACHE_SIZE {
+            self.cache.clear();
+        }
+        self.cache.insert(command.clone(), output);
+        if self.current.as_ref() == Some(&command) {
+            self.shown = Some(command);
+            return true;
+        }
+        false
+    }
+
+    pub fn output(&self) -> Option<Result<&[Line], &MenuError>> {
+        self.shown
+            .as_ref()
+            .and_then(|command| self.cache.get(command))
+            .map(|output| output.as_ref().map(Vec::as_slice))
+    }
+
+    /// Runs the command for the item under the cursor, unless its output is cached.
+    /// The subscription is identified by the command, so moving the cursor drops it,
+    /// which kills the process if it is still running.
+    pub fn subscription(&self) -> Subscription<Message> {
+        struct PreviewRun;
+
+        let Some(command) = self.current.clone().filter(|_| !self.is_cached()) else {
+            return Subscription::none();
+        };
+        let max_bytes = self.max_bytes;
+        let highlighting = self.syntax_theme.zip(self.extension.clone());
+        subscription::unfold(
+            (std::any::TypeId::of::<PreviewRun>(), command.clone()),
+            Some(command),
+            move |command| {
+                let highlighting = highlighting.clone();
+                async move {
+                    match command {
+                        Some(command) => {
+                            let result = run(&command, max_bytes)
+                                .await
+                                .map(|output| style(&output, highlighting));
+                            (Message::PreviewFinished(command, result), None)
+                        }
+                        None => iced::futures::future::pending().await,
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// Colors the output by its escape sequences, or else as code if there is a syntax for it
+fn style(output: &str, highlighting: Option<(SyntaxTheme, String)>) -> Vec<Line> {
+    match highlighting {
+        Some((theme, extension)) if !output.contains('\x1b') => {
+            highlight::highlight(output, &extension, theme).unwrap_or_else(|| ansi::parse(output))
+        }
+        _ => ansi::parse(output),
+    }
+}
+
+/// Runs `command` with the shell and returns at most `max_bytes` of what it prints,
+/// including errors. The process is killed once that much has been read.
+async fn run(command: &str, max_bytes: usize) -> Result<String, MenuError> {
+    let spawn_error = |e: std::io::Error| MenuError::Preview {
+        command: command.to_owned(),
+        message: e.to_string(),
+    };
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{command}"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(spawn_error)?;
+    let mut output = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        stdout
+            .take(max_bytes as u64)
+            .read_to_end(&mut output)
+            .await
+            .map_err(spawn_error)?;
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(command: &str, data: &ItemData) -> String {
+        Preview::new(command.to_owned(), 1024, None).expand(data)
+    }
+
+    #[test]
+    fn expands_fields() {
+        let data = ItemData::new(String::from("it's"), None);
+        assert_eq!(expand("cat {key}", &data), "cat 'it'\\''s'");
+        assert_eq!(expand("cat {value}", &data), "cat 'it'\\''s'");
+        assert_eq!(expand("echo {description}", &data), "echo ''");
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        let data = ItemData::new(String::from("a"), None);
+        assert_eq!(expand("echo a{b", &data), "echo a{b");
+        assert_eq!(
+            expand("awk '{print $1}' {key}", &data),
+            "awk '{print $1}' 'a'"
+        );
+        assert_eq!(expand("echo ${HOME}{key}", &data), "echo ${HOME}'a'");
+        assert_eq!(expand("{ echo; } {{key}}", &data), "{ echo; } {'a'}");
+    }
+}
@@ -0,0 +1,366 @@
+use crate::app::Message;
+use crate::error::MenuError;
+use crate::item::{Item, ItemData, JsonItem};
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// A long-lived process that provides items for each query.
+/// Messages are exchanged with it as newline-delimited JSON over its stdin and stdout.
+pub struct Provider {
+    command: ProviderCommand,
+    sender: Option<mpsc::UnboundedSender<Request>>,
+    /// The id of the most recent query
+    query_id: u64,
+    /// Whether the most recent query is still waiting for items
+    pending: bool,
+    /// A query that was made before the provider started
+    unsent: Option<String>,
+}
+
+#[derive(Clone)]
+struct ProviderCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+/// Messages sent from icedmenu to the provider
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Query { id: u64, query: String },
+    Cancel { id: u64 },
+}
+
+/// Messages sent from the provider to icedmenu
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response {
+    Items {
+        #[serde(default)]
+        id: Option<u64>,
+        items: Vec<JsonItem>,
+    },
+    Prompt {
+        prompt: String,
+    },
+    Message {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Ready(mpsc::UnboundedSender<Request>),
+    Items(Option<u64>, Vec<Item>),
+    Prompt(String),
+    Message(String),
+    Error(MenuError),
+    Exited(MenuError),
+}
+
+impl Provider {
+    pub fn new(cli_args: Vec<String>) -> Self {
+        let program = cli_args
+            .first()
+            .unwrap_or_else(|| unreachable!("Clap should force at least one argument for provider"))
+            .to_string();
+        let args = cli_args.iter().skip(1).map(String::from).collect();
+        Self {
+            command: ProviderCommand { program, args },
+            sender: None,
+            query_id: 0,
+            pending: false,
+            unsent: None,
+        }
+    }
+
+    /// Sends a new query to the provider, cancelling the previous one if it is unanswered
+    pub fn query(&mut self, query: &str) {
+        if self.pending {
+            self.send(Request::Cancel { id: self.query_id });
+        }
+        self.query_id += 1;
+        self.pending = true;
+        if self.sender.is_some() {
+            self.send(Request::Query {
+                id: self.query_id,
+                query: query.to_owned(),
+            });
+        } else {
+            self.unsent = Some(query.to_owned());
+        }
+    }
+
+    pub fn connect(&mut self, sender: mpsc::UnboundedSender<Request>) {
+        self.sender = Some(sender);
+        if let Some(query) = self.unsent.take() {
+            self.send(Request::Query {
+                id: self.query_id,
+                query,
+            });
+        }
+    }
+
+    /// Whether items with the given query id are for the most recent query.
+    /// Items without an id are always accepted.
+    pub fn accept(&mut self, id: Option<u64>) -> bool {
+        match id {
+            Some(id) if id != self.query_id => false,
+            _ => {
+                self.pending = false;
+                true
+            }
+        }
+    }
+
+    fn send(&self, request: Request) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.unbounded_send(request);
+        }
+    }
+
+    /// Starts the provider and relays messages between it and the menu until it exits
+    pub fn subscription(&self) -> Subscription<Message> {
+        struct ProviderProcess;
+
+        let command = self.command.clone();
+        subscription::channel(
+            std::any::TypeId::of::<ProviderProcess>(),
+            16,
+            move |mut output| {
+                let command = command.clone();
+                async move {
+                    let exit_error = command.run(&mut output).await;
+                    let _ = output
+                        .send(Message::Provider(Event::Exited(exit_error)))
+                        .await;
+                    iced::futures::future::pending().await
+                }
+            },
+        )
+    }
+}
+
+impl ProviderCommand {
+    async fn run(&self, output: &mut mpsc::Sender<Message>) -> MenuError {
+        let error = |message: String| MenuError::Provider {
+            program: self.program.clone(),
+            message,
+        };
+        let mut child = match Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return error(format!("Could not start: {e}")),
+        };
+        let mut stdin = child.stdin.take();
+        let mut lines = BufReader::new(
+            child
+                .stdout
+                .take()
+                .expect("Provider stdout should be piped"),
+        )
+        .lines();
+
+        let (sender, mut requests) = mpsc::unbounded();
+        let _ = output.send(Message::Provider(Event::Ready(sender))).await;
+
+        loop {
+            tokio::select! {
+                request = requests.next(), if stdin.is_some() => {
+                    let (Some(request), Some(input)) = (request, &mut stdin) else {
+                        // The menu is gone, so the provider has nothing more to read
+                        stdin = None;
+                        continue;
+                    };
+                    let mut line = serde_json::to_string(&request)
+                        .expect("Provider requests should always serialize");
+                    line.push('\n');
+                    if let Err(e) = input.write_all(line.as_bytes()).await {
+                        return error(format!("Could not send request: {e}"));
+                    }
+                    let _ = input.flush().await;
+                }
+                line = lines.next_line() => {
+                    let event = match line {
+                        Ok(Some(line)) if line.trim().is_empty() => continue,
+                        Ok(Some(line)) => match serde_json::from_str::<Response>(&line) {
+                            Ok(response) => Self::event(response, &error),
+                            Err(e) => Event::Error(error(format!("Invalid message: {e}"))),
+                        },
+                        Ok(None) => break,
+                        Err(e) => return error(format!("Could not read output: {e}")),
+                    };
+                    let _ = output.send(Message::Provider(event)).await;
+                }
+            }
+        }
+
+        match child.wait().await {
+            Ok(status) => error(format!("Exited unexpectedly ({status})")),
+            Err(e) => error(e.to_string()),
+        }
+    }
+
+    fn event(response: Response, error: &impl Fn(String) -> MenuError) -> Event {
+        match response {
+            Response::Items { id, items } => Event::Items(
+                id,
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, item)| Item::new(i, ItemData::from(item)))
+                    .collect(),
+            ),
+            Response::Prompt { prompt } => Event::Prompt(prompt),
+            Response::Message { message } => Event::Message(message),
+            Response::Error { message } => Event::Error(error(message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Starts `examples/provider.py`, returning the menu's side of it and its events
+    fn start() -> (Provider, mpsc::Receiver<Message>) {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/provider.py");
+        let provider = Provider::new(vec![String::from("python3"), String::from(script)]);
+        let command = provider.command.clone();
+        let (mut output, events) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let error = command.run(&mut output).await;
+            let _ = output.send(Message::Provider(Event::Exited(error))).await;
+        });
+        (provider, events)
+    }
+
+    async fn next_event(events: &mut mpsc::Receiver<Message>) -> Event {
+        let message = tokio::time::timeout(Duration::from_secs(10), events.next())
+            .await
+            .expect("the provider should respond");
+        match message {
+            Some(Message::Provider(event)) => event,
+            other => panic!("expected a provider event, got {other:?}"),
+        }
+    }
+
+    async fn connect() -> (Provider, mpsc::Receiver<Message>) {
+        let (mut provider, mut events) = start();
+        match next_event(&mut events).await {
+            Event::Ready(sender) => provider.connect(sender),
+            other => panic!("expected the provider to start, got {other:?}"),
+        }
+        (provider, events)
+    }
+
+    fn keys(items: &[Item]) -> Vec<&str> {
+        items.iter().map(|item| item.data.key.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn sends_prompt_and_message() {
+        let (_provider, mut events) = connect().await;
+        assert!(matches!(next_event(&mut events).await, Event::Prompt(p) if p == "Launch:"));
+        assert!(
+            matches!(next_event(&mut events).await, Event::Message(m) if m == "5 applications")
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_queries() {
+        let (mut provider, mut events) = start();
+        // Queries made before the provider starts are sent once it does
+        provider.query("fi");
+        match next_event(&mut events).await {
+            Event::Ready(sender) => provider.connect(sender),
+            other => panic!("expected the provider to start, got {other:?}"),
+        }
+        next_event(&mut events).await;
+        next_event(&mut events).await;
+        match next_event(&mut events).await {
+            Event::Items(id, items) => {
+                assert!(provider.accept(id));
+                assert_eq!(keys(&items), ["Firefox", "Files"]);
+                assert_eq!(items[1].data.value.as_deref(), Some("nautilus"));
+            }
+            other => panic!("expected items, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_errors() {
+        let (mut provider, mut events) = connect().await;
+        next_event(&mut events).await;
+        next_event(&mut events).await;
+        provider.query("error");
+        match next_event(&mut events).await {
+            Event::Error(MenuError::Provider { message, .. }) => {
+                assert_eq!(message, "Example error")
+            }
+            other => panic!("expected an error, got {other:?}"),
+        }
+        // The provider exits once the menu is gone
+        drop(provider);
+        assert!(matches!(
+            next_event(&mut events).await,
+            Event::Exited(MenuError::Provider { .. })
+        ));
+    }
+
+    #[test]
+    fn cancels_unanswered_queries() {
+        let mut provider = Provider::new(vec![String::from("provider")]);
+        let (sender, mut requests) = mpsc::unbounded();
+        provider.connect(sender);
+        provider.query("a");
+        provider.query("ab");
+        assert!(provider.accept(Some(2)));
+        provider.query("abc");
+        let requests: Vec<String> = std::iter::from_fn(|| requests.try_next().ok().flatten())
+            .map(|request| serde_json::to_string(&request).unwrap())
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                r#"{"type":"query","id":1,"query":"a"}"#,
+                r#"{"type":"cancel","id":1}"#,
+                r#"{"type":"query","id":2,"query":"ab"}"#,
+                r#"{"type":"query","id":3,"query":"abc"}"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn drops_items_of_cancelled_queries() {
+        let (mut provider, mut events) = connect().await;
+        next_event(&mut events).await;
+        next_event(&mut events).await;
+        // The first query is cancelled by the second, before any items arrive
+        provider.query("te");
+        provider.query("calc");
+        let mut accepted = Vec::new();
+        for _ in 0..2 {
+            match next_event(&mut events).await {
+                Event::Items(id, items) if provider.accept(id) => accepted.push(items),
+                Event::Items(..) => {}
+                other => panic!("expected items, got {other:?}"),
+            }
+        }
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(keys(&accepted[0]), ["Calculator"]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use iced::futures::executor::block_on;
+use iced::{application, Application, Color, Point, Size};
+use iced_native::renderer::Style;
+use iced_native::user_interface::{Cache, UserInterface};
+use iced_native::widget::{operation, text_input};
+use iced_wgpu::{wgpu, Backend, Renderer, Viewport};
+
+use crate::app::{Flags, IcedMenu, DEFAULT_FONT_SIZE};
+use crate::exit::Exit;
+use crate::layout::query::QUERY_INPUT_ID;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Renders the menu for `flags` to a PNG image at `path` without opening a window
+pub fn run(mut flags: Flags, path: &Path) -> ! {
+    flags.read_stdin();
+    let (menu, _) = IcedMenu::new(flags);
+    let (width, height) = menu.size();
+    let result = render(&menu, width, height).and_then(|pixels| {
+        write_png(path, width, height, &pixels)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))
+    });
+    match result {
+        Ok(()) => Exit::Accept.exit(),
+        Err(e) => {
+            eprintln!("{e}");
+            Exit::Error.exit()
+        }
+    }
+}
+
+/// Draws the menu offscreen with the same renderer as the window, returning RGBA pixels
+fn render(menu: &IcedMenu, width: u32, height: u32) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 {
+        return Err(format!(
+            "The layout has no size to render ({width}x{height})"
+        ));
+    }
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        .ok_or("Could not find a graphics adapter")?;
+    let (device, queue) = block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits {
+                max_bind_groups: 2,
+                ..wgpu::Limits::downlevel_defaults()
+            },
+        },
+        None,
+    ))
+    .map_err(|e| format!("Could not create a graphics device: {e}"))?;
+
+    let settings = iced_wgpu::Settings {
+        default_text_size: DEFAULT_FONT_SIZE,
+        ..Default::default()
+    };
+    let mut renderer = Renderer::new(Backend::new(&device, settings, FORMAT));
+    let theme = menu.theme();
+    let appearance = application::StyleSheet::appearance(&theme, &menu.style());
+
+    let size = Size::new(width as f32, height as f32);
+    let mut ui = UserInterface::build(menu.view(), size, Cache::default(), &mut renderer);
+    // The query is focused as soon as the window opens
+    let mut focus = operation::focusable::focus(text_input::Id::new(QUERY_INPUT_ID).into());
+    ui.operate(&renderer, &mut focus);
+    let style = Style {
+        text_color: appearance.text_color,
+    };
+    // Keep the mouse away, so that nothing is hovered
+    ui.draw(&mut renderer, &theme, &style, Point::new(-1.0, -1.0));
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    clear(&mut encoder, &view, appearance.background_color);
+    let mut staging_belt = wgpu::util::StagingBelt::new(10 * 1024);
+    let viewport = Viewport::with_physical_size(Size::new(width, height), 1.0);
+    renderer.with_primitives(|backend, primitives| {
+        backend.present::<&str>(
+            &device,
+            &mut staging_belt,
+            &mut encoder,
+            &view,
+            primitives,
+            &viewport,
+            &[],
+        );
+    });
+    staging_belt.finish();
+
+    // Rows of the copy must be padded to a multiple of 256 bytes
+    let row_bytes = width as usize * 4;
+    let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (padded_row_bytes * height as usize) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_bytes as u32),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Could not read the rendered image: {e}"))?;
+    let padded = slice.get_mapped_range();
+    Ok(padded
+        .chunks(padded_row_bytes)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect())
+}
+
+fn clear(encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, color: Color) {
+    let [r, g, b, a] = color.into_linear().map(f64::from);
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+}
+
+fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)
+}
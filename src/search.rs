@@ -0,0 +1,442 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::cli::{CaseSensitivity, MatchingAlgorithm};
+use crate::columns::{ColumnRanges, ColumnView};
+use crate::item::{Item, ItemData};
+use crate::matcher::{self, Matcher};
+
+/// An item field that is searched, and how much its scores count for
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchField {
+    pub name: String,
+    pub weight: f32,
+}
+
+impl SearchField {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            weight: 1.0,
+        }
+    }
+
+    /// Parses a field name with an optional weight, like `description:0.5`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.rsplit_once(':') {
+            Some((name, weight)) => Ok(Self {
+                name: name.to_owned(),
+                weight: weight
+                    .parse()
+                    .map_err(|_| format!("`{weight}` is not a valid weight"))?,
+            }),
+            None => Ok(Self::new(s)),
+        }
+    }
+}
+
+/// The text of one searched field of an item
+pub struct Haystack<'a> {
+    pub text: Cow<'a, str>,
+    pub weight: f32,
+}
+
+/// How a single search term is matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TermKind {
+    /// `term`: matched with the active matching algorithm
+    Default,
+    /// `'term`: the item must contain the term
+    Substring,
+    /// `^term`: the item must start with the term
+    Prefix,
+    /// `term$`: the item must end with the term
+    Suffix,
+    /// `^term$`: the item must be equal to the term
+    Exact,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Term {
+    kind: TermKind,
+    text: String,
+    /// `!term`: the item must not match the term
+    negated: bool,
+}
+
+impl Term {
+    fn parse(token: &str) -> Option<Self> {
+        let (negated, mut text) = match token.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+        // Negated terms are matched literally, as in fzf
+        let mut kind = if negated {
+            TermKind::Substring
+        } else {
+            TermKind::Default
+        };
+        if let Some(rest) = text.strip_prefix('\'') {
+            kind = TermKind::Substring;
+            text = rest;
+        } else {
+            let prefix = text.strip_prefix('^');
+            if let Some(rest) = prefix {
+                text = rest;
+            }
+            let suffix = text.strip_suffix('$').filter(|rest| !rest.ends_with('\\'));
+            if let Some(rest) = suffix {
+                text = rest;
+            }
+            kind = match (prefix.is_some(), suffix.is_some()) {
+                (true, true) => TermKind::Exact,
+                (true, false) => TermKind::Prefix,
+                (false, true) => TermKind::Suffix,
+                (false, false) => kind,
+            };
+        }
+        let text = text.replace("\\$", "$");
+        (!text.is_empty()).then_some(Self {
+            kind,
+            text,
+            negated,
+        })
+    }
+}
+
+/// A query in fzf's extended search syntax: space-separated terms that must all match,
+/// where each term may be a `|`-separated group of alternatives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    groups: Vec<Vec<Term>>,
+}
+
+impl Search {
+    pub fn parse(query: &str, algorithm: MatchingAlgorithm) -> Self {
+        // Regular expressions use the same special characters, so they are not split up
+        if algorithm == MatchingAlgorithm::Regex {
+            let groups = if query.is_empty() {
+                Vec::new()
+            } else {
+                vec![vec![Term {
+                    kind: TermKind::Default,
+                    text: query.to_owned(),
+                    negated: false,
+                }]]
+            };
+            return Self { groups };
+        }
+
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut continues_group = false;
+        for token in tokens(query) {
+            if token == "|" {
+                continues_group = !groups.is_empty();
+                continue;
+            }
+            let Some(term) = Term::parse(&token) else {
+                continue;
+            };
+            match groups.last_mut() {
+                Some(group) if continues_group => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            continues_group = false;
+        }
+        Self { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+/// Splits a query on whitespace, except for spaces escaped with a backslash
+fn tokens(query: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(' ') => current.push(' '),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    result.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+/// A term's score, and the haystack it matched along with the match indices.
+/// Negated terms do not match any haystack.
+type TermMatch = (u32, Option<(usize, Vec<usize>)>);
+
+/// Scores items against a `Search` using the active matcher for plain terms
+pub struct Searcher {
+    pub algorithm: MatchingAlgorithm,
+    fields: Vec<SearchField>,
+    /// Which columns of the key are searched
+    nth: Option<ColumnRanges>,
+    delimiter: Option<char>,
+    matcher: Box<dyn Matcher>,
+    substring: Box<dyn Matcher>,
+    prefix: Box<dyn Matcher>,
+    suffix: Box<dyn Matcher>,
+    exact: Box<dyn Matcher>,
+}
+
+impl Searcher {
+    pub fn new(
+        algorithm: MatchingAlgorithm,
+        case: CaseSensitivity,
+        fields: Vec<SearchField>,
+        nth: Option<ColumnRanges>,
+        delimiter: Option<char>,
+        normalize: bool,
+    ) -> Self {
+        Self {
+            algorithm,
+            fields,
+            nth,
+            delimiter,
+            matcher: matcher::new(algorithm, case, normalize),
+            substring: matcher::new(MatchingAlgorithm::Substring, case, normalize),
+            prefix: matcher::new(MatchingAlgorithm::Prefix, case, normalize),
+            suffix: matcher::new(MatchingAlgorithm::Suffix, case, normalize),
+            exact: matcher::new(MatchingAlgorithm::Exact, case, normalize),
+        }
+    }
+
+    fn matcher_for(&self, kind: TermKind) -> &dyn Matcher {
+        match kind {
+            TermKind::Default => self.matcher.as_ref(),
+            TermKind::Substring => self.substring.as_ref(),
+            TermKind::Prefix => self.prefix.as_ref(),
+            TermKind::Suffix => self.suffix.as_ref(),
+            TermKind::Exact => self.exact.as_ref(),
+        }
+    }
+
+    /// Scores each item against `search`. Nothing is scored for an empty search, and
+    /// selected items are left unscored since they are always shown.
+    pub fn score_items(&self, search: &Search, items: &mut [Item]) {
+        for item in items {
+            let result = if search.is_empty() || item.selected {
+                None
+            } else {
+                self.score_item(search, &item.data)
+            };
+            (item.score, item.match_indices) = match result {
+                Some((score, match_indices)) => (Some(score), match_indices),
+                None => (None, HashMap::new()),
+            };
+        }
+    }
+
+    /// Scores an item's search fields, returning the match indices of each field that matched
+    pub fn score_item(
+        &self,
+        search: &Search,
+        data: &ItemData,
+    ) -> Option<(u32, HashMap<String, Vec<usize>>)> {
+        let mut key_view = None;
+        let haystacks: Vec<Haystack> = self
+            .fields
+            .iter()
+            .map(|field| {
+                let text = match (field.name.as_str(), &self.nth, &data.display_key) {
+                    // `--nth` picks columns of the whole key
+                    ("key", Some(nth), _) => {
+                        let view = ColumnView::new(&data.key, self.delimiter, nth);
+                        let text = Cow::Owned(view.text.clone());
+                        key_view = Some(Cow::Owned(view));
+                        text
+                    }
+                    // Otherwise the columns shown by `--with-nth` are searched
+                    ("key", None, Some(view)) => {
+                        key_view = Some(Cow::Borrowed(view));
+                        Cow::Borrowed(view.text.as_str())
+                    }
+                    (name, _, _) => data.field(name).unwrap_or_default(),
+                };
+                Haystack {
+                    text,
+                    weight: field.weight,
+                }
+            })
+            .collect();
+        let (score, indices) = self.match_fields(search, &haystacks)?;
+        let match_indices = self
+            .fields
+            .iter()
+            .zip(indices)
+            .filter(|(_, indices)| !indices.is_empty())
+            .map(|(field, indices)| {
+                let indices = match (&key_view, field.name.as_str()) {
+                    (Some(view), "key") => view.original_indices(&indices),
+                    _ => indices,
+                };
+                (field.name.clone(), indices)
+            })
+            .collect();
+        Some((score, match_indices))
+    }
+
+    /// Returns the combined score of all terms, and for each haystack the (sorted) indices
+    /// matched by any of them. Each term can match in any of the haystacks.
+    pub fn match_fields(
+        &self,
+        search: &Search,
+        haystacks: &[Haystack],
+    ) -> Option<(u32, Vec<Vec<usize>>)> {
+        let mut score = 0;
+        let mut indices = vec![Vec::new(); haystacks.len()];
+        for group in search.groups.iter() {
+            // The best-scoring alternative in each group counts towards the total
+            let (group_score, group_indices) = group
+                .iter()
+                .filter_map(|term| self.match_term(term, haystacks))
+                .max_by_key(|(score, _)| *score)?;
+            score += group_score;
+            if let Some((i, group_indices)) = group_indices {
+                indices[i].extend(group_indices);
+            }
+        }
+        for field_indices in indices.iter_mut() {
+            field_indices.sort_unstable();
+            field_indices.dedup();
+        }
+        Some((score, indices))
+    }
+
+    /// Scores a term against the best-matching haystack, returning its index and match indices
+    fn match_term(&self, term: &Term, haystacks: &[Haystack]) -> Option<TermMatch> {
+        let matcher = self.matcher_for(term.kind);
+        let mut results = haystacks.iter().enumerate().filter_map(|(i, haystack)| {
+            let (score, indices) = matcher.match_indices(&haystack.text, &term.text)?;
+            Some(((score as f32 * haystack.weight) as u32, Some((i, indices))))
+        });
+        if term.negated {
+            // Negated terms must not match in any field
+            match results.next() {
+                Some(_) => None,
+                None => Some((0, None)),
+            }
+        } else {
+            results.max_by_key(|(score, _)| *score)
+        }
+    }
+}
+
+/// The indices of the items that match `search`, best first. Everything matches an empty
+/// search, in the order of the input.
+pub fn rank_items(search: &Search, items: &[Item]) -> Vec<usize> {
+    let show_all = search.is_empty();
+    let mut candidates: Vec<&Item> = items
+        .iter()
+        .filter(|item| show_all || item.score.is_some())
+        .collect();
+    candidates.sort_by(|a, b| b.cmp(a));
+    candidates.iter().map(|item| item.index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(kind: TermKind, text: &str) -> Term {
+        Term {
+            kind,
+            text: text.to_owned(),
+            negated: false,
+        }
+    }
+
+    fn negated(kind: TermKind, text: &str) -> Term {
+        Term {
+            negated: true,
+            ..term(kind, text)
+        }
+    }
+
+    fn parse(query: &str) -> Vec<Vec<Term>> {
+        Search::parse(query, MatchingAlgorithm::Fuzzy).groups
+    }
+
+    #[test]
+    fn terms() {
+        use TermKind::*;
+        assert_eq!(
+            parse("foo 'bar ^baz qux$ ^quux$"),
+            [
+                vec![term(Default, "foo")],
+                vec![term(Substring, "bar")],
+                vec![term(Prefix, "baz")],
+                vec![term(Suffix, "qux")],
+                vec![term(Exact, "quux")],
+            ]
+        );
+    }
+
+    #[test]
+    fn negated_terms() {
+        use TermKind::*;
+        assert_eq!(
+            parse("!foo !^bar !baz$ !'qux"),
+            [
+                vec![negated(Substring, "foo")],
+                vec![negated(Prefix, "bar")],
+                vec![negated(Suffix, "baz")],
+                vec![negated(Substring, "qux")],
+            ]
+        );
+    }
+
+    #[test]
+    fn alternatives() {
+        use TermKind::*;
+        assert_eq!(
+            parse("^a | b$ | 'c d"),
+            [
+                vec![term(Prefix, "a"), term(Suffix, "b"), term(Substring, "c")],
+                vec![term(Default, "d")],
+            ]
+        );
+        // A `|` with nothing on one side is ignored
+        assert_eq!(parse("| a |"), [vec![term(Default, "a")]]);
+    }
+
+    #[test]
+    fn escapes() {
+        use TermKind::*;
+        assert_eq!(parse(r"foo\ bar"), [vec![term(Default, "foo bar")]]);
+        assert_eq!(parse(r"cost\$"), [vec![term(Default, "cost$")]]);
+        assert_eq!(parse(r"^\$"), [vec![term(Prefix, "$")]]);
+    }
+
+    #[test]
+    fn empty_terms() {
+        assert!(Search::parse("", MatchingAlgorithm::Fuzzy).is_empty());
+        assert!(Search::parse("  ! ^ ' $ |", MatchingAlgorithm::Fuzzy).is_empty());
+    }
+
+    #[test]
+    fn regex_queries_are_not_split() {
+        let search = Search::parse("^a | b$", MatchingAlgorithm::Regex);
+        assert_eq!(search.groups, [vec![term(TermKind::Default, "^a | b$")]]);
+        assert!(Search::parse("", MatchingAlgorithm::Regex).is_empty());
+    }
+}
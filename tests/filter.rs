@@ -0,0 +1,221 @@
+//! Runs `icedmenu --filter` on fixed input, checking the ranking of the matches and the
+//! positions printed by `--scores`
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const ITEMS: &str = "foo bar\nfoobar\nbar foo\nbarfoo\nfood\nFoo\n";
+
+/// Filters `input` with the query and extra arguments, returning what was printed and the
+/// exit code
+fn filter(query: &str, args: &[&str], input: &str) -> (String, i32) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_icedmenu"))
+        .args(["--filter", query])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("icedmenu should start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        output.status.code().unwrap(),
+    )
+}
+
+/// Each match of `ITEMS` as its key and the positions that matched, best first
+fn matches(query: &str, args: &[&str]) -> Vec<(String, String)> {
+    let args = [args, &["--scores"]].concat();
+    let (output, _) = filter(query, &args, ITEMS);
+    output
+        .lines()
+        .map(|line| {
+            let [_score, positions, key] = line.splitn(3, '\t').collect::<Vec<_>>()[..] else {
+                panic!("expected a score, positions and a key: {line:?}");
+            };
+            (key.to_owned(), positions.to_owned())
+        })
+        .collect()
+}
+
+fn expected(matches: &[(&str, &str)]) -> Vec<(String, String)> {
+    matches
+        .iter()
+        .map(|(key, positions)| (key.to_string(), positions.to_string()))
+        .collect()
+}
+
+#[test]
+fn fuzzy() {
+    assert_eq!(
+        matches("fb", &[]),
+        expected(&[("foo bar", "key:0,4"), ("foobar", "key:0,3")])
+    );
+    // Ties are kept in the order of the input
+    assert_eq!(
+        matches("foo", &["--matching", "fuzzy"]),
+        expected(&[
+            ("foo bar", "key:0,1,2"),
+            ("foobar", "key:0,1,2"),
+            ("food", "key:0,1,2"),
+            ("Foo", "key:0,1,2"),
+            ("bar foo", "key:4,5,6"),
+            ("barfoo", "key:3,4,5"),
+        ])
+    );
+    // Smart case
+    assert_eq!(matches("Foo", &[]), expected(&[("Foo", "key:0,1,2")]));
+}
+
+#[test]
+fn exact() {
+    assert_eq!(
+        matches("foo", &["--matching", "exact"]),
+        expected(&[("Foo", "key:0,1,2")])
+    );
+}
+
+#[test]
+fn prefix() {
+    // Shorter items come first
+    assert_eq!(
+        matches("foo", &["--matching", "prefix"]),
+        expected(&[
+            ("Foo", "key:0,1,2"),
+            ("food", "key:0,1,2"),
+            ("foobar", "key:0,1,2"),
+            ("foo bar", "key:0,1,2"),
+        ])
+    );
+}
+
+#[test]
+fn substring() {
+    assert_eq!(
+        matches("foo", &["--matching", "substring"]),
+        expected(&[
+            ("Foo", "key:0,1,2"),
+            ("food", "key:0,1,2"),
+            ("foobar", "key:0,1,2"),
+            ("foo bar", "key:0,1,2"),
+            ("barfoo", "key:3,4,5"),
+            ("bar foo", "key:4,5,6"),
+        ])
+    );
+}
+
+#[test]
+fn word_prefix() {
+    assert_eq!(
+        matches("foo", &["--matching", "word-prefix"]),
+        expected(&[
+            ("Foo", "key:0,1,2"),
+            ("food", "key:0,1,2"),
+            ("foobar", "key:0,1,2"),
+            ("foo bar", "key:0,1,2"),
+            ("bar foo", "key:4,5,6"),
+        ])
+    );
+}
+
+#[test]
+fn regex() {
+    assert_eq!(
+        matches("o.ar$", &["--matching", "regex"]),
+        expected(&[("foobar", "key:2,3,4,5")])
+    );
+    // The extended syntax doesn't apply to regular expressions
+    assert_eq!(
+        matches("^bar|d$", &["--matching", "regex"]),
+        expected(&[
+            ("barfoo", "key:0,1,2"),
+            ("bar foo", "key:0,1,2"),
+            ("food", "key:3"),
+        ])
+    );
+}
+
+#[test]
+fn extended_prefix_and_suffix() {
+    assert_eq!(
+        matches("^foo", &[]),
+        expected(&[
+            ("Foo", "key:0,1,2"),
+            ("food", "key:0,1,2"),
+            ("foobar", "key:0,1,2"),
+            ("foo bar", "key:0,1,2"),
+        ])
+    );
+    assert_eq!(
+        matches("foo$", &[]),
+        expected(&[
+            ("Foo", "key:0,1,2"),
+            ("barfoo", "key:3,4,5"),
+            ("bar foo", "key:4,5,6"),
+        ])
+    );
+    assert_eq!(matches("^foo$", &[]), expected(&[("Foo", "key:0,1,2")]));
+}
+
+#[test]
+fn extended_substring() {
+    // Unlike fuzzy matching, the characters must be next to each other
+    assert_eq!(matches("'oba", &[]), expected(&[("foobar", "key:2,3,4")]));
+}
+
+#[test]
+fn extended_negation() {
+    assert_eq!(matches("!bar", &[]), expected(&[("food", ""), ("Foo", "")]));
+    assert_eq!(
+        matches("fo !^foo", &[]),
+        expected(&[("bar foo", "key:4,5"), ("barfoo", "key:3,4")])
+    );
+}
+
+#[test]
+fn extended_alternatives() {
+    assert_eq!(
+        matches("^bar | d$", &[]),
+        expected(&[
+            ("food", "key:3"),
+            ("barfoo", "key:0,1,2"),
+            ("bar foo", "key:0,1,2"),
+        ])
+    );
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(filter("foo", &[], ITEMS).1, 0);
+    let (output, code) = filter("xyz", &[], ITEMS);
+    assert_eq!((output.as_str(), code), ("", 1));
+}
+
+#[test]
+fn stdout_closed_early() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_icedmenu"))
+        .args(["--filter", "foo"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("icedmenu should start");
+    // Nothing is written until all of stdin is read, so this is before any output
+    drop(child.stdout.take());
+    let items = ITEMS.repeat(1000);
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(items.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.status.code(), Some(0));
+}
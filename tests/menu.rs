@@ -0,0 +1,11 @@
+use icedmenu::MenuBuilder;
+
+#[test]
+fn off_the_main_thread() {
+    // Test threads are named after the test, so this is not the main thread
+    let error = MenuBuilder::new().items(["one"]).run().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "A menu can only be shown from the main thread"
+    );
+}
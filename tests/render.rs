@@ -0,0 +1,127 @@
+//! Renders the bundled configs with `icedmenu --render-to-png` and compares them with the
+//! images in `tests/render`. Rendering needs a graphics adapter, which can be a software one
+//! like lavapipe or llvmpipe, so these tests are skipped unless they are asked for:
+//!
+//! ```sh
+//! cargo test --test render -- --ignored
+//! ```
+//!
+//! After changing how the menu looks on purpose, set `UPDATE_RENDER_IMAGES=1` to replace the
+//! stored images with the new renders.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const ITEMS: &str = "one\ntwo\nthree\n";
+/// How much a color channel may differ, for small differences between graphics drivers
+const CHANNEL_TOLERANCE: u8 = 16;
+/// The share of pixels that may differ by more than that, for antialiasing of text
+const PIXEL_TOLERANCE: f64 = 0.005;
+
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn read_png(path: &Path) -> Image {
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|e| panic!("could not open {}: {e}", path.display()));
+    let mut reader = png::Decoder::new(file).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+    pixels.truncate(info.buffer_size());
+    Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    }
+}
+
+/// Renders `config` with `ITEMS` to a PNG image in the temporary directory
+fn render(config: &Path) -> PathBuf {
+    let name = config.file_stem().unwrap().to_string_lossy();
+    let image =
+        std::env::temp_dir().join(format!("icedmenu-render-{}-{name}.png", std::process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_icedmenu"))
+        .arg("--config")
+        .arg(config)
+        .arg("--render-to-png")
+        .arg(&image)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("icedmenu should start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(ITEMS.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "rendering {} failed: {}",
+        config.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    image
+}
+
+/// Renders `config` and compares it with `tests/render/NAME.png`
+fn assert_renders_like_before(config: &str, name: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let expected_path = root.join("tests/render").join(format!("{name}.png"));
+    let actual_path = render(&root.join(config));
+    if std::env::var_os("UPDATE_RENDER_IMAGES").is_some() {
+        std::fs::copy(&actual_path, &expected_path).unwrap();
+        std::fs::remove_file(&actual_path).unwrap();
+        return;
+    }
+
+    let expected = read_png(&expected_path);
+    let actual = read_png(&actual_path);
+    let kept = format!("the render was kept at {}", actual_path.display());
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height),
+        "{config} rendered at a different size; {kept}"
+    );
+    let differing = actual
+        .pixels
+        .chunks_exact(4)
+        .zip(expected.pixels.chunks_exact(4))
+        .filter(|(a, e)| {
+            a.iter()
+                .zip(*e)
+                .any(|(a, e)| a.abs_diff(*e) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let allowed = (PIXEL_TOLERANCE * (expected.width * expected.height) as f64) as usize;
+    assert!(
+        differing <= allowed,
+        "{differing} pixels of {config} differ from {}; {kept}",
+        expected_path.display()
+    );
+    std::fs::remove_file(&actual_path).unwrap();
+}
+
+#[test]
+#[ignore = "needs a graphics adapter, like lavapipe or llvmpipe"]
+fn default_config() {
+    assert_renders_like_before("default-config.kdl", "default-config");
+}
+
+#[test]
+#[ignore = "needs a graphics adapter, like lavapipe or llvmpipe"]
+fn dmenu() {
+    assert_renders_like_before("examples/dmenu.kdl", "dmenu");
+}
+
+#[test]
+#[ignore = "needs a graphics adapter, like lavapipe or llvmpipe"]
+fn purple() {
+    assert_renders_like_before("examples/purple.kdl", "purple");
+}